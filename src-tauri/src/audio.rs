@@ -0,0 +1,449 @@
+use std::fs;
+use std::io::{Cursor, ErrorKind};
+use std::path::Path;
+
+use serde::Serialize;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Audio formats osu! beatmaps ship that can be decoded here.
+pub const DECODABLE_EXTENSIONS: [&str; 3] = ["mp3", "ogg", "wav"];
+/// ReplayGain 2.0 reference level.
+const REPLAY_GAIN_REFERENCE_LUFS: f64 = -18.0;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// Decoded PCM, channels interleaved.
+pub struct Audio {
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub samples: Vec<f32>,
+}
+
+impl Audio {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1)
+    }
+}
+
+/// EBU R128 measurements of a track. Levels are `None` for digital silence.
+#[derive(Serialize, Clone, Copy)]
+pub struct Loudness {
+    pub integrated_lufs: Option<f64>,
+    /// Highest sample level (not true peak).
+    pub peak_dbfs: Option<f64>,
+    /// Gain that brings the track to the ReplayGain 2.0 reference of -18 LUFS.
+    pub replay_gain_db: Option<f64>,
+    pub duration_ms: u64,
+}
+
+pub fn is_decodable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| DECODABLE_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+pub fn decode(path: &Path) -> Result<Audio, String> {
+    let fail = |e: SymphoniaError| format!("Failed to decode {}: {}", path.display(), e);
+    let file = fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(fail)?
+        .format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| format!("No audio track in {}", path.display()))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(44_100);
+    let mut channels = track.codec_params.channels.map(|channels| channels.count()).unwrap_or(2);
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(fail)?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(fail(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                sample_rate = spec.rate;
+                channels = spec.channels.count();
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                samples.extend_from_slice(buffer.samples());
+            }
+            // A corrupt frame is skipped, the way players do.
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(fail(e)),
+        }
+    }
+
+    Ok(Audio {
+        sample_rate,
+        channels,
+        samples,
+    })
+}
+
+/// Plays `audio` at `rate` times the speed without changing its pitch, using
+/// WSOLA: overlapping windowed frames are read `rate` times faster than they
+/// are written, each nudged to where it best continues the previous one.
+pub fn time_stretch(audio: &Audio, rate: f64) -> Audio {
+    let channels = audio.channels.max(1);
+    let frames = audio.frames();
+    let frame_len = ((audio.sample_rate as usize * 40 / 1000) & !1).max(2);
+    let hop = frame_len / 2;
+    let tolerance = audio.sample_rate as usize * 8 / 1000;
+    let window: Vec<f32> = (0..frame_len)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / frame_len as f32).cos())
+        .collect();
+    let mono: Vec<f32> = audio
+        .samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    let out_frames = (frames as f64 / rate).ceil() as usize;
+    let mut output = vec![0.0f32; (out_frames + frame_len) * channels];
+    let mut weight = vec![0.0f32; out_frames + frame_len];
+    let mut previous: Option<usize> = None;
+
+    for out_pos in (0..out_frames).step_by(hop) {
+        let nominal = (out_pos as f64 * rate) as usize;
+        if nominal >= frames {
+            break;
+        }
+        let start = match previous {
+            Some(previous) => best_offset(&mono, previous + hop, nominal, tolerance, hop),
+            None => nominal,
+        };
+        for (i, w) in window.iter().enumerate() {
+            let source = start + i;
+            if source >= frames {
+                break;
+            }
+            let target = out_pos + i;
+            for channel in 0..channels {
+                output[target * channels + channel] += audio.samples[source * channels + channel] * w;
+            }
+            weight[target] += w;
+        }
+        previous = Some(start);
+    }
+
+    for (frame, w) in output.chunks_mut(channels).zip(&weight) {
+        if *w > 1e-3 {
+            frame.iter_mut().for_each(|sample| *sample /= w);
+        }
+    }
+    output.truncate(out_frames * channels);
+    Audio {
+        sample_rate: audio.sample_rate,
+        channels,
+        samples: output,
+    }
+}
+
+/// Start within `tolerance` of `nominal` whose first `len` samples best match
+/// `natural`, the continuation of the previous frame. Every fourth sample is
+/// compared, which is plenty for picking an offset and keeps long songs fast.
+fn best_offset(mono: &[f32], natural: usize, nominal: usize, tolerance: usize, len: usize) -> usize {
+    let len = len.min(mono.len().saturating_sub(natural));
+    if len == 0 {
+        return nominal;
+    }
+    let low = nominal.saturating_sub(tolerance);
+    let high = (nominal + tolerance).min(mono.len().saturating_sub(len));
+    (low..=high)
+        .map(|candidate| {
+            let score: f32 = (0..len)
+                .step_by(4)
+                .map(|i| mono[natural + i] * mono[candidate + i])
+                .sum();
+            (candidate, score)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(candidate, _)| candidate)
+        .unwrap_or(nominal)
+}
+
+/// 16-bit PCM WAV bytes, which osu! plays without extra codecs.
+pub fn encode_wav(audio: &Audio) -> Result<Vec<u8>, String> {
+    let spec = hound::WavSpec {
+        channels: audio.channels as u16,
+        sample_rate: audio.sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut cursor, spec).map_err(|e| e.to_string())?;
+    for sample in &audio.samples {
+        let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+        writer.write_sample(value).map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(cursor.into_inner())
+}
+
+/// Integrated loudness per EBU R128 (BS.1770 K-weighting, 400 ms blocks with
+/// 75% overlap, absolute and relative gating) plus sample peak and duration.
+/// All channels are weighted equally, which is exact for mono and stereo.
+pub fn loudness(audio: &Audio) -> Loudness {
+    let channels = audio.channels.max(1);
+    let frames = audio.frames();
+    let rate = f64::from(audio.sample_rate.max(1));
+    let step = ((rate / 10.0) as usize).max(1);
+
+    // Sum of squared K-weighted samples over all channels, per 100 ms step.
+    let mut steps = vec![0.0f64; frames.div_ceil(step)];
+    for channel in 0..channels {
+        let mut shelf = Biquad::k_weighting_shelf(rate);
+        let mut high_pass = Biquad::k_weighting_high_pass(rate);
+        for (frame, sample) in audio.samples.iter().skip(channel).step_by(channels).enumerate() {
+            let filtered = high_pass.process(shelf.process(f64::from(*sample)));
+            steps[frame / step] += filtered * filtered;
+        }
+    }
+    let block_powers: Vec<f64> = steps
+        .windows(4)
+        .map(|block| block.iter().sum::<f64>() / (4 * step) as f64)
+        .collect();
+    let to_lufs = |power: f64| -0.691 + 10.0 * power.log10();
+    let mean_power = |powers: &[f64]| powers.iter().sum::<f64>() / powers.len() as f64;
+
+    let above_absolute: Vec<f64> = block_powers
+        .into_iter()
+        .filter(|power| *power > 0.0 && to_lufs(*power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    let integrated_lufs = (!above_absolute.is_empty())
+        .then(|| {
+            let threshold = to_lufs(mean_power(&above_absolute)) + RELATIVE_GATE_LU;
+            let gated: Vec<f64> = above_absolute
+                .iter()
+                .copied()
+                .filter(|power| to_lufs(*power) > threshold)
+                .collect();
+            (!gated.is_empty()).then(|| to_lufs(mean_power(&gated)))
+        })
+        .flatten();
+
+    let peak = audio.samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    Loudness {
+        integrated_lufs,
+        peak_dbfs: (peak > 0.0).then(|| 20.0 * f64::from(peak).log10()),
+        replay_gain_db: integrated_lufs.map(|lufs| REPLAY_GAIN_REFERENCE_LUFS - lufs),
+        duration_ms: (frames as f64 * 1000.0 / rate) as u64,
+    }
+}
+
+/// Gain that brings a track to `target_lufs` without pushing its peak past
+/// `ceiling_dbfs`, so loud tracks are turned down and quiet ones only as far
+/// up as they can go without clipping.
+pub fn normalization_gain_db(loudness: &Loudness, target_lufs: f64, ceiling_dbfs: f64) -> f64 {
+    let Some(integrated) = loudness.integrated_lufs else {
+        return 0.0;
+    };
+    let gain = target_lufs - integrated;
+    match loudness.peak_dbfs {
+        Some(peak) => gain.min(ceiling_dbfs - peak),
+        None => gain,
+    }
+}
+
+pub fn apply_gain(audio: &mut Audio, gain_db: f64) {
+    let factor = 10f64.powf(gain_db / 20.0) as f32;
+    audio.samples.iter_mut().for_each(|sample| *sample *= factor);
+}
+
+/// Second-order IIR section (direct form I).
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    /// BS.1770 stage 1: high shelf modelling the acoustic effect of the head,
+    /// derived for any sample rate.
+    fn k_weighting_shelf(rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+
+    /// BS.1770 stage 2: the RLB high-pass.
+    fn k_weighting_high_pass(rate: f64) -> Self {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stereo sine at `level_dbfs` peak on both channels.
+    fn sine(frequency: f64, level_dbfs: f64, sample_rate: u32, frames: usize) -> Audio {
+        let amplitude = 10f64.powf(level_dbfs / 20.0);
+        let samples = (0..frames)
+            .flat_map(|i| {
+                let value = (amplitude
+                    * (2.0 * std::f64::consts::PI * frequency * i as f64 / f64::from(sample_rate)).sin())
+                    as f32;
+                [value, value]
+            })
+            .collect();
+        Audio {
+            sample_rate,
+            channels: 2,
+            samples,
+        }
+    }
+
+    fn measured(loudness: &Loudness) -> f64 {
+        loudness.integrated_lufs.expect("sine is not silent")
+    }
+
+    #[test]
+    fn stereo_sine_at_minus_23_dbfs_measures_minus_23_lufs() {
+        let loudness = loudness(&sine(997.0, -23.0, 48000, 48000 * 20));
+
+        assert!((measured(&loudness) - -23.0).abs() < 0.005, "{}", measured(&loudness));
+        assert!((loudness.peak_dbfs.unwrap() - -23.0).abs() < 0.01);
+        assert!((loudness.replay_gain_db.unwrap() - 5.0).abs() < 0.005);
+        assert_eq!(loudness.duration_ms, 20000);
+    }
+
+    #[test]
+    fn silence_has_no_level() {
+        let loudness = loudness(&Audio {
+            sample_rate: 44100,
+            channels: 2,
+            samples: vec![0.0; 44100 * 2],
+        });
+
+        assert!(loudness.integrated_lufs.is_none());
+        assert!(loudness.peak_dbfs.is_none());
+        assert!(loudness.replay_gain_db.is_none());
+        assert_eq!(loudness.duration_ms, 1000);
+    }
+
+    #[test]
+    fn stretching_shortens_by_the_rate_and_keeps_loudness() {
+        let original = sine(997.0, -23.0, 48000, 48000 * 5 + 1);
+        let stretched = time_stretch(&original, 1.5);
+
+        assert_eq!(stretched.frames(), (original.frames() as f64 / 1.5).ceil() as usize);
+        assert_eq!(stretched.channels, 2);
+        assert_eq!(stretched.sample_rate, 48000);
+        let difference = measured(&loudness(&stretched)) - measured(&loudness(&original));
+        assert!(difference.abs() < 0.2, "{}", difference);
+    }
+
+    #[test]
+    fn normalization_gain_stops_at_the_peak_ceiling() {
+        let level = |integrated_lufs: Option<f64>, peak_dbfs: Option<f64>| Loudness {
+            integrated_lufs,
+            peak_dbfs,
+            replay_gain_db: None,
+            duration_ms: 0,
+        };
+
+        // Turned down to the target.
+        assert!((normalization_gain_db(&level(Some(-8.0), Some(-0.1)), -14.0, -1.0) - -6.0).abs() < 1e-9);
+        // Turned up only until the peak reaches the ceiling.
+        assert!((normalization_gain_db(&level(Some(-20.0), Some(-3.0)), -14.0, -1.0) - 2.0).abs() < 1e-9);
+        assert!((normalization_gain_db(&level(Some(-20.0), Some(-10.0)), -14.0, -1.0) - 6.0).abs() < 1e-9);
+        assert_eq!(normalization_gain_db(&level(None, None), -14.0, -1.0), 0.0);
+    }
+
+    #[test]
+    fn gain_scales_every_sample() {
+        let mut audio = Audio {
+            sample_rate: 44100,
+            channels: 2,
+            samples: vec![0.25, -0.5, 0.0, 0.1],
+        };
+        apply_gain(&mut audio, 20.0 * 2f64.log10());
+
+        let expected = [0.5, -1.0, 0.0, 0.2];
+        for (sample, expected) in audio.samples.iter().zip(expected) {
+            assert!((sample - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn wav_is_16_bit_pcm_with_clamped_samples() {
+        let audio = Audio {
+            sample_rate: 44100,
+            channels: 2,
+            samples: vec![0.0, 0.5, -0.5, 1.5, -2.0, 1.0],
+        };
+        let bytes = encode_wav(&audio).unwrap();
+
+        let reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 44100);
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(spec.sample_format, hound::SampleFormat::Int);
+        let samples: Vec<i16> = reader.into_samples().map(Result::unwrap).collect();
+        assert_eq!(samples, [0, 16383, -16383, i16::MAX, -i16::MAX, i16::MAX]);
+    }
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let args = std::env::args().skip(1).collect();
+    std::process::exit(osu_mappack_creator_v2_lib::cli::run(args))
+}
@@ -0,0 +1,294 @@
+use std::path::Path;
+
+use crate::commands::{self, Beatmap, PackBeatmapInput, VideoPolicy};
+use crate::jobs::CancelToken;
+use crate::manifest::{self, PackManifest, MANIFEST_VERSION};
+use crate::transform::ColumnTransform;
+
+const USAGE: &str = "\
+Usage: mappack-cli <command> [options]
+
+Commands:
+  index <songs-dir>                 Build or refresh the beatmap index
+  import-db <osu-dir>               Import the index from osu!.db
+  search <songs-dir> <query>...     List indexed beatmaps matching a filter query
+  pack --manifest <file>            Create a pack described by a JSON manifest
+  pack --title <t> --artist <a> --creator <c> [--output <dir>] [--extra] [--keep-folder] [--storyboard] [--videos] [--rate <r>] [--normalize]
+       [--mirror | --columns <2,1,4,3> | --shuffle <seed>] <file.osu>...
+                                    Create a pack from the given difficulties
+  pack ... --save-manifest <file>   Also save the pack as a manifest for later rebuilds
+
+Options:
+  --storyboard                      Include each difficulty's storyboard (pack)
+  --rate <r>                        Pack rate-changed copies, e.g. 1.2 (pack)
+  --mirror                          Mirror mania columns (pack)
+  --columns <list>                  Move column i to the i-th listed column, counting from 1 (pack)
+  --shuffle <seed>                  Shuffle mania columns reproducibly from <seed> (pack)
+  --normalize                       Normalize audio loudness, written as WAV (pack)
+  --videos                          Keep background videos instead of stripping them (pack)
+  --json                            Print results as JSON (search, pack)
+  -h, --help                        Show this help";
+
+/// Entry point of the headless binary. Returns the process exit code.
+pub fn run(args: Vec<String>) -> i32 {
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let outcome = match command.as_str() {
+        "index" => run_index(rest),
+        "import-db" => run_import_db(rest),
+        "search" => run_search(rest),
+        "pack" => run_pack(rest),
+        "-h" | "--help" | "help" => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        other => Err(format!("Unknown command '{}'\n\n{}", other, USAGE)),
+    };
+
+    match outcome {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            1
+        }
+    }
+}
+
+fn single_path(args: &[String], name: &str) -> Result<String, String> {
+    match args {
+        [path] => Ok(path.clone()),
+        _ => Err(format!("Expected exactly one <{}> argument", name)),
+    }
+}
+
+fn run_index(args: &[String]) -> Result<(), String> {
+    let songs_dir = single_path(args, "songs-dir")?;
+    let songs_dir = Path::new(&songs_dir);
+    if !songs_dir.exists() {
+        return Err("Provided path does not exist".into());
+    }
+
+    let result = commands::build_index(songs_dir, &CancelToken::default(), |progress| {
+        eprint!(
+            "\r[{}/{}] {} beatmaps",
+            progress.scanned_folders, progress.total_folders, progress.scanned
+        );
+    })?;
+    eprintln!();
+    println!(
+        "Indexed {} beatmaps in {} ms ({} added, {} changed, {} deleted) -> {}",
+        result.total,
+        result.duration_ms,
+        result.added.len(),
+        result.changed.len(),
+        result.deleted.len(),
+        result.index_path
+    );
+    Ok(())
+}
+
+fn run_import_db(args: &[String]) -> Result<(), String> {
+    let install_dir = single_path(args, "osu-dir")?;
+    let result = commands::import_osu_db_index(Path::new(&install_dir))?;
+    println!(
+        "Imported {} beatmaps for {} from osu!.db -> {}",
+        result.index.total, result.player_name, result.index.index_path
+    );
+    Ok(())
+}
+
+fn run_search(args: &[String]) -> Result<(), String> {
+    let (json, args) = take_flag(args, "--json");
+    let Some((songs_dir, terms)) = args.split_first() else {
+        return Err("Expected <songs-dir> and a query".into());
+    };
+    let beatmaps = commands::load_index(Path::new(songs_dir))?;
+    let page = commands::query_page(&beatmaps, &terms.join(" "), 0, usize::MAX)?;
+    if json {
+        print_json(&page.beatmaps)?;
+    } else {
+        for beatmap in &page.beatmaps {
+            println!("{}", describe(beatmap));
+        }
+        eprintln!("{} results", page.total_results);
+    }
+    Ok(())
+}
+
+fn run_pack(args: &[String]) -> Result<(), String> {
+    let (json, args) = take_flag(args, "--json");
+    let (include_extra_files, args) = take_flag(&args, "--extra");
+    let (keep_folder, args) = take_flag(&args, "--keep-folder");
+    let (include_storyboard, args) = take_flag(&args, "--storyboard");
+    let (include_videos, args) = take_flag(&args, "--videos");
+    let (normalize_audio, args) = take_flag(&args, "--normalize");
+    let (mirror, args) = take_flag(&args, "--mirror");
+
+    let mut manifest_path = None;
+    let mut title = None;
+    let mut artist = None;
+    let mut creator = None;
+    let mut output_dir = None;
+    let mut save_path = None;
+    let mut rate = None;
+    let mut column_order = None;
+    let mut shuffle_seed = None;
+    let mut paths = Vec::new();
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        let slot = match arg.as_str() {
+            "--manifest" => &mut manifest_path,
+            "--title" => &mut title,
+            "--artist" => &mut artist,
+            "--creator" => &mut creator,
+            "--output" => &mut output_dir,
+            "--save-manifest" => &mut save_path,
+            "--rate" => &mut rate,
+            "--columns" => &mut column_order,
+            "--shuffle" => &mut shuffle_seed,
+            flag if flag.starts_with("--") => return Err(format!("Unknown option '{}'", flag)),
+            _ => {
+                paths.push(absolute(&arg)?);
+                continue;
+            }
+        };
+        *slot = Some(iter.next().ok_or_else(|| format!("Missing value for {}", arg))?);
+    }
+
+    let rate = rate
+        .map(|rate| rate.parse::<f64>().map_err(|_| format!("Invalid --rate '{}'", rate)))
+        .transpose()?;
+    let columns = column_transform(mirror, column_order, shuffle_seed)?;
+
+    let manifest = match manifest_path {
+        Some(path) => {
+            if !paths.is_empty() {
+                return Err("Beatmap paths cannot be combined with --manifest".into());
+            }
+            manifest::load(Path::new(&path))?
+        }
+        None => PackManifest {
+            version: MANIFEST_VERSION,
+            title: title.ok_or("Missing --title")?,
+            artist: artist.ok_or("Missing --artist")?,
+            creator: creator.ok_or("Missing --creator")?,
+            output_dir: output_dir.as_deref().map(absolute).transpose()?,
+            include_extra_files,
+            keep_folder,
+            video_policy: if include_videos {
+                VideoPolicy::Include
+            } else {
+                VideoPolicy::Strip
+            },
+            normalize_audio,
+            beatmaps: pack_inputs(paths, include_storyboard, rate, columns)?,
+        },
+    };
+    if let Some(path) = save_path {
+        manifest::save(Path::new(&path), &manifest)?;
+    }
+
+    for warning in commands::validate_pack_manifest(&manifest).warnings {
+        match warning.path {
+            Some(path) => eprintln!("warning: {}: {}", path, warning.message),
+            None => eprintln!("warning: {}", warning.message),
+        }
+    }
+
+    let result = commands::build_pack(&manifest, &CancelToken::default(), |progress| {
+        eprint!(
+            "\r[{}/{}] {} files, {} KiB",
+            progress.beatmaps_done,
+            progress.total_beatmaps,
+            progress.files_written,
+            progress.bytes_written / 1024
+        );
+    })?;
+    eprintln!();
+    if json {
+        print_json(&result)?;
+    } else {
+        println!("{}", result.osz_path);
+    }
+    Ok(())
+}
+
+/// Builds pack entries that keep each difficulty's own name, HP and OD.
+fn pack_inputs(
+    paths: Vec<String>,
+    include_storyboard: bool,
+    rate: Option<f64>,
+    columns: Option<ColumnTransform>,
+) -> Result<Vec<PackBeatmapInput>, String> {
+    Ok(commands::beatmap_details(paths)?
+        .into_iter()
+        .map(|detail| PackBeatmapInput {
+            path: detail.path,
+            new_version: String::new(),
+            hp_drain_rate: detail.metadata.hp_drain_rate,
+            overall_difficulty: detail.metadata.overall_difficulty,
+            include_storyboard,
+            rate,
+            columns: columns.clone(),
+        })
+        .collect())
+}
+
+fn column_transform(
+    mirror: bool,
+    order: Option<String>,
+    seed: Option<String>,
+) -> Result<Option<ColumnTransform>, String> {
+    match (mirror, order, seed) {
+        (false, None, None) => Ok(None),
+        (true, None, None) => Ok(Some(ColumnTransform::Mirror)),
+        (false, Some(order), None) => {
+            let mapping = order
+                .split(',')
+                .map(|column| match column.trim().parse::<u32>() {
+                    Ok(column) if column > 0 => Ok(column - 1),
+                    _ => Err(format!("Invalid --columns '{}'", order)),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Some(ColumnTransform::Permute { mapping }))
+        }
+        (false, None, Some(seed)) => {
+            let seed = seed.parse().map_err(|_| format!("Invalid --shuffle seed '{}'", seed))?;
+            Ok(Some(ColumnTransform::Shuffle { seed }))
+        }
+        _ => Err("Use only one of --mirror, --columns and --shuffle".into()),
+    }
+}
+
+/// Absolute paths keep a saved manifest valid from any folder.
+fn absolute(path: &str) -> Result<String, String> {
+    std::path::absolute(path)
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| format!("Invalid path {}: {}", path, e))
+}
+
+fn take_flag(args: &[String], flag: &str) -> (bool, Vec<String>) {
+    let present = args.iter().any(|arg| arg == flag);
+    (present, args.iter().filter(|arg| *arg != flag).cloned().collect())
+}
+
+fn describe(beatmap: &Beatmap) -> String {
+    let metadata = &beatmap.metadata;
+    let stars = beatmap
+        .star_rating
+        .map(|stars| format!(" {:.2}*", stars))
+        .unwrap_or_default();
+    format!(
+        "{} - {} ({}) [{}]{}\t{}",
+        metadata.artist, metadata.title, metadata.creator, metadata.version, stars, beatmap.path
+    )
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    println!("{}", text);
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::Path;
+
+use crate::osu_db::{DbReader, DbWriter};
+
+/// Version written to new collection.db files; osu! stable accepts any
+/// version it has ever produced.
+const DEFAULT_VERSION: i32 = 20250107;
+/// An empty name plus its hash count, and an empty hash string.
+const MIN_COLLECTION_SIZE: usize = 5;
+const MIN_HASH_SIZE: usize = 1;
+
+pub struct Collection {
+    pub name: String,
+    pub hashes: Vec<String>,
+}
+
+pub struct CollectionDatabase {
+    pub version: i32,
+    pub collections: Vec<Collection>,
+}
+
+pub fn read_collection_db(path: &Path) -> Result<CollectionDatabase, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_collection_db(&bytes)
+}
+
+fn parse_collection_db(bytes: &[u8]) -> Result<CollectionDatabase, String> {
+    let mut reader = DbReader::new(bytes);
+
+    let version = reader.i32()?;
+    let count = reader.i32()?.max(0) as usize;
+    let mut collections = Vec::with_capacity(reader.capacity(count, MIN_COLLECTION_SIZE));
+    for _ in 0..count {
+        let name = reader.string()?;
+        let size = reader.i32()?.max(0) as usize;
+        let mut hashes = Vec::with_capacity(reader.capacity(size, MIN_HASH_SIZE));
+        for _ in 0..size {
+            hashes.push(reader.string()?);
+        }
+        collections.push(Collection { name, hashes });
+    }
+
+    Ok(CollectionDatabase {
+        version,
+        collections,
+    })
+}
+
+impl CollectionDatabase {
+    /// Adds `hashes` to the collection called `name`, creating it when missing
+    /// and skipping hashes it already contains. Returns how many were added.
+    pub fn merge(&mut self, name: &str, hashes: &[String]) -> usize {
+        let index = match self.collections.iter().position(|collection| collection.name == name) {
+            Some(index) => index,
+            None => {
+                self.collections.push(Collection {
+                    name: name.to_string(),
+                    hashes: Vec::new(),
+                });
+                self.collections.len() - 1
+            }
+        };
+        let collection = &mut self.collections[index];
+        let mut added = 0;
+        for hash in hashes {
+            if !collection.hashes.contains(hash) {
+                collection.hashes.push(hash.clone());
+                added += 1;
+            }
+        }
+        added
+    }
+}
+
+impl Default for CollectionDatabase {
+    fn default() -> Self {
+        CollectionDatabase {
+            version: DEFAULT_VERSION,
+            collections: Vec::new(),
+        }
+    }
+}
+
+pub fn write_collection_db(path: &Path, database: &CollectionDatabase) -> Result<(), String> {
+    let mut writer = DbWriter::default();
+    writer.i32(database.version);
+    writer.i32(database.collections.len() as i32);
+    for collection in &database.collections {
+        writer.string(&collection.name);
+        writer.i32(collection.hashes.len() as i32);
+        for hash in &collection.hashes {
+            writer.string(hash);
+        }
+    }
+
+    // Write next to the original and swap it in so a failed write never
+    // leaves the player with a truncated collection.db.
+    let temp_path = path.with_extension("db.tmp");
+    fs::write(&temp_path, writer.into_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+    fs::rename(&temp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_creates_collections_and_skips_known_hashes() {
+        let mut database = CollectionDatabase::default();
+        assert_eq!(database.merge("Pack", &hashes(&["a", "b"])), 2);
+        assert_eq!(database.merge("Pack", &hashes(&["b", "c"])), 1);
+        assert_eq!(database.merge("Other", &hashes(&["a"])), 1);
+
+        assert_eq!(database.collections.len(), 2);
+        assert_eq!(database.collections[0].hashes, hashes(&["a", "b", "c"]));
+    }
+
+    #[test]
+    fn written_database_reads_back() {
+        let dir = std::env::temp_dir().join(format!("collection-db-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("collection.db");
+
+        let mut database = CollectionDatabase::default();
+        database.merge("Pack", &hashes(&["d41d8cd98f00b204e9800998ecf8427e"]));
+        database.merge("", &[]);
+        write_collection_db(&path, &database).unwrap();
+        let read = read_collection_db(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let read = read.unwrap();
+        assert_eq!(read.version, DEFAULT_VERSION);
+        let names: Vec<_> = read.collections.iter().map(|collection| collection.name.as_str()).collect();
+        assert_eq!(names, ["Pack", ""]);
+        assert_eq!(read.collections[0].hashes, database.collections[0].hashes);
+        assert!(read.collections[1].hashes.is_empty());
+    }
+
+    #[test]
+    fn huge_counts_fail_instead_of_allocating() {
+        let mut writer = DbWriter::default();
+        writer.i32(DEFAULT_VERSION);
+        writer.i32(i32::MAX);
+        assert!(parse_collection_db(&writer.into_bytes()).is_err());
+
+        let mut writer = DbWriter::default();
+        writer.i32(DEFAULT_VERSION);
+        writer.i32(1);
+        writer.string("Pack");
+        writer.i32(i32::MAX);
+        writer.string("abc");
+        assert!(parse_collection_db(&writer.into_bytes()).is_err());
+    }
+}
@@ -1,21 +1,61 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "gui")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Instant, UNIX_EPOCH};
 use chrono::Local;
+use md5::{Digest, Md5};
 use serde::Serialize;
-use serde_json;
-use tauri::{Window, Emitter};
+#[cfg(feature = "gui")]
+use tauri::{Emitter, Manager, State, Window};
+#[cfg(feature = "gui")]
 use tauri_plugin_dialog::DialogExt;
-use regex::Regex;
 use zip::write::FileOptions;
 use zip::CompressionMethod;
 
+use crate::audio;
+#[cfg(feature = "gui")]
+use crate::collection_db;
+use crate::difficulty::{self, ManiaDifficulty};
+use crate::hitsounds::{self, SampleSlot};
+use crate::jobs::CancelToken;
+#[cfg(feature = "gui")]
+use crate::jobs::JobRegistry;
+#[cfg(feature = "gui")]
+use crate::manifest;
+use crate::manifest::PackManifest;
+use crate::osu_db::{self, OsuDbBeatmap};
+use crate::osu_file::{Event, HitObject, OsuFile};
+use crate::search;
+use crate::storyboard;
+use crate::transform::{self, ColumnTransform};
+
 const INDEX_FILE_NAME: &str = "beatmap_index.json";
 const DELETE_OSU_TEMPLATE: &str = include_str!("../resources/delete.osu");
 const DELETE_BG_BYTES: &[u8] = include_bytes!("../resources/delete.jpg");
 const DELETE_AUDIO_BYTES: &[u8] = include_bytes!("../resources/delete.mp3");
+const OSU_DB_FILE_NAME: &str = "osu!.db";
+#[cfg(feature = "gui")]
+const COLLECTION_DB_FILE_NAME: &str = "collection.db";
+#[cfg(feature = "gui")]
+const OSU_EXE_FILE_NAME: &str = "osu!.exe";
 const LOG_FILE_NAME: &str = "osu-mappack-creator-v2.log";
+const PROGRESS_INTERVAL_MS: u128 = 100;
+const LATEST_FORMAT_VERSION: u32 = 14;
+const MAX_FILE_NAME_LEN: usize = 255;
+const MIN_RATE: f64 = 0.5;
+const MAX_RATE: f64 = 2.0;
+/// Loudness normalized pack audio is brought to, with peaks kept under the ceiling.
+const NORMALIZE_TARGET_LUFS: f64 = -14.0;
+const NORMALIZE_CEILING_DBFS: f64 = -1.0;
+const RESERVED_FILE_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
 
 fn log_file_path() -> PathBuf {
     let mut path = std::env::temp_dir();
@@ -50,12 +90,29 @@ fn log_error(message: &str) {
     }
 }
 
-#[derive(Serialize, serde::Deserialize)]
+#[derive(Serialize, serde::Deserialize, Clone)]
 pub struct Beatmap {
     pub id: String,
     pub path: String,
+    #[serde(default)]
+    pub metadata: BeatmapMetadata,
+    #[serde(default)]
+    pub timing: BeatmapTiming,
+    #[serde(default)]
+    pub star_rating: Option<f64>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub modified: u64,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub md5: Option<String>,
+    #[serde(default)]
+    pub last_played: Option<i64>,
 }
 
+#[cfg(feature = "gui")]
 #[derive(Serialize)]
 pub struct BeatmapPage {
     pub beatmaps: Vec<Beatmap>,
@@ -64,23 +121,56 @@ pub struct BeatmapPage {
 }
 
 #[derive(Serialize)]
+pub struct BeatmapQueryPage {
+    pub beatmaps: Vec<Beatmap>,
+    pub total_results: usize,
+    pub total_pages: usize,
+}
+
+#[derive(Serialize, Clone)]
 pub struct BeatmapIndexResult {
     pub beatmaps: Vec<Beatmap>,
     pub index_path: String,
     pub total: usize,
     pub duration_ms: u128,
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub deleted: Vec<String>,
+    pub cancelled: bool,
 }
 
 #[derive(Serialize, serde::Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct BeatmapMetadata {
     pub title: String,
+    pub title_unicode: String,
     pub artist: String,
+    pub artist_unicode: String,
     pub creator: String,
     pub version: String,
+    pub source: String,
+    pub tags: String,
     pub audio_filename: String,
     pub hp_drain_rate: f32,
     pub overall_difficulty: f32,
     pub background_file: String,
+    pub mode: u8,
+    pub key_count: u32,
+    pub note_count: usize,
+    pub long_note_count: usize,
+    pub ln_percentage: f32,
+    pub first_object_time: i32,
+    pub last_object_time: i32,
+}
+
+#[derive(Serialize, serde::Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BeatmapTiming {
+    pub min_bpm: f64,
+    pub max_bpm: f64,
+    pub dominant_bpm: f64,
+    pub drain_time_ms: i32,
+    pub total_length_ms: i32,
 }
 
 #[derive(Serialize)]
@@ -88,49 +178,312 @@ pub struct BeatmapDetail {
     pub id: String,
     pub path: String,
     pub metadata: BeatmapMetadata,
+    pub timing: BeatmapTiming,
+    pub difficulty: Option<ManiaDifficulty>,
+    /// Taken from the loudness cache; `None` until the audio has been analysed.
+    pub loudness: Option<audio::Loudness>,
+}
+
+#[derive(Serialize)]
+pub struct OsuDbImportResult {
+    pub songs_path: String,
+    pub player_name: String,
+    pub index: BeatmapIndexResult,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize)]
+pub struct CollectionEntry {
+    pub name: String,
+    pub beatmaps: Vec<Beatmap>,
+    pub missing: Vec<String>,
 }
 
+#[cfg(feature = "gui")]
 #[derive(Serialize, Clone)]
 pub struct IndexProgressPayload {
+    pub job_id: u64,
+    #[serde(flatten)]
+    pub progress: IndexProgress,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone)]
+pub struct IndexFinishedPayload {
+    pub job_id: u64,
+    pub result: BeatmapIndexResult,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone)]
+pub struct JobFailedPayload {
+    pub job_id: u64,
+    pub error: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct IndexProgress {
     pub folder: String,
     pub scanned: usize,
+    pub scanned_folders: usize,
+    pub total_folders: usize,
+    pub eta_ms: Option<u64>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Serialize, serde::Deserialize, Clone)]
 pub struct PackBeatmapInput {
     pub path: String,
+    /// Version before the rate and column suffixes; empty keeps the
+    /// difficulty's own name.
     pub new_version: String,
     pub hp_drain_rate: f32,
     pub overall_difficulty: f32,
+    /// Inline the set's .osb and copy storyboard files; when off, storyboard
+    /// events are stripped so the pack never points at missing files.
+    #[serde(default)]
+    pub include_storyboard: bool,
+    /// Playback speed of a rate-changed copy, e.g. 1.2; `None` keeps the original.
+    #[serde(default)]
+    pub rate: Option<f64>,
+    /// Mirror, reorder or shuffle the columns of a mania difficulty.
+    #[serde(default)]
+    pub columns: Option<ColumnTransform>,
 }
 
-#[derive(Serialize)]
+impl PackBeatmapInput {
+    fn rate_change(&self) -> Option<f64> {
+        self.rate.filter(|rate| (rate - 1.0).abs() > f64::EPSILON)
+    }
+}
+
+/// What happens to the background videos of packed difficulties.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoPolicy {
+    /// Drop Video events; packs stay small.
+    #[default]
+    Strip,
+    /// Copy each video into the pack under a numbered name.
+    Include,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PackStage {
+    Rewrite,
+    Copy,
+    Compress,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PackProgress {
+    pub stage: PackStage,
+    pub file: String,
+    pub files_written: usize,
+    pub bytes_written: u64,
+    pub beatmaps_done: usize,
+    pub total_beatmaps: usize,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone)]
+pub struct PackProgressPayload {
+    pub job_id: u64,
+    #[serde(flatten)]
+    pub progress: PackProgress,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone)]
+pub struct PackFinishedPayload {
+    pub job_id: u64,
+    pub result: PackCreationResult,
+}
+
+#[derive(Serialize, Clone)]
 pub struct PackCreationResult {
     pub osz_path: String,
-    pub folder_path: String,
+    /// Plain folder copy of the pack, only written when requested.
+    pub folder_path: Option<String>,
     pub file_count: usize,
+    /// MD5 of every generated .osu file, as osu! stores them in collection.db.
+    pub md5s: Vec<String>,
 }
 
-#[tauri::command]
-pub fn load_cached_index(osu_path: String) -> Result<BeatmapIndexResult, String> {
-    let songs_dir = Path::new(&osu_path);
-    let index_path = songs_dir.join(INDEX_FILE_NAME);
+#[derive(Serialize)]
+pub struct PackIssue {
+    pub code: &'static str,
+    pub message: String,
+    /// Beatmap the issue belongs to, `None` for pack-wide issues.
+    pub path: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+pub struct PackValidationReport {
+    pub errors: Vec<PackIssue>,
+    pub warnings: Vec<PackIssue>,
+    /// Version each beatmap gets in the pack, in manifest order; `None` when
+    /// the beatmap can't be read.
+    pub versions: Vec<Option<String>>,
+}
+
+impl PackValidationReport {
+    fn error(&mut self, code: &'static str, path: Option<&str>, message: String) {
+        self.errors.push(PackIssue {
+            code,
+            message,
+            path: path.map(str::to_string),
+        });
+    }
+
+    fn warning(&mut self, code: &'static str, path: Option<&str>, message: String) {
+        self.warnings.push(PackIssue {
+            code,
+            message,
+            path: path.map(str::to_string),
+        });
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize)]
+pub struct CollectionExportResult {
+    pub collection_path: String,
+    pub added: usize,
+    pub total: usize,
+}
 
+fn read_index(index_path: &Path) -> Result<Vec<Beatmap>, String> {
     if !index_path.exists() {
         return Err("Index file not found".into());
     }
 
-    let content = fs::read_to_string(&index_path).map_err(|e| e.to_string())?;
-    let beatmaps: Vec<Beatmap> = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let content = fs::read_to_string(index_path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+/// The index of `songs_dir`, read from disk.
+pub fn load_index(songs_dir: &Path) -> Result<Vec<Beatmap>, String> {
+    read_index(&songs_dir.join(INDEX_FILE_NAME))
+}
+
+/// Parsed index files, kept between commands so every keystroke in the
+/// search box doesn't re-read and deserialize the whole index. Entries are
+/// dropped whenever the app rewrites an index.
+#[cfg(feature = "gui")]
+#[derive(Default)]
+pub struct IndexCache {
+    indexes: Mutex<HashMap<PathBuf, Arc<Vec<Beatmap>>>>,
+}
+
+#[cfg(feature = "gui")]
+impl IndexCache {
+    pub fn get(&self, songs_dir: &Path) -> Result<Arc<Vec<Beatmap>>, String> {
+        let index_path = songs_dir.join(INDEX_FILE_NAME);
+        if let Some(beatmaps) = self.lock().get(&index_path) {
+            return Ok(beatmaps.clone());
+        }
+        let beatmaps = Arc::new(read_index(&index_path)?);
+        self.lock().insert(index_path, beatmaps.clone());
+        Ok(beatmaps)
+    }
+
+    pub fn invalidate(&self, songs_dir: &Path) {
+        self.lock().remove(&songs_dir.join(INDEX_FILE_NAME));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Arc<Vec<Beatmap>>>> {
+        self.indexes.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Loudness of every audio file analysed so far, keyed by path and stamped
+/// with the file's modification time and size. Decoding a whole track is
+/// slow, so the details view asks for it separately and only once per file.
+#[cfg(feature = "gui")]
+#[derive(Default)]
+pub struct LoudnessCache {
+    entries: Mutex<HashMap<PathBuf, CachedLoudness>>,
+}
+
+#[cfg(feature = "gui")]
+struct CachedLoudness {
+    stamp: (u64, u64),
+    loudness: Option<audio::Loudness>,
+}
+
+#[cfg(feature = "gui")]
+impl LoudnessCache {
+    /// Loudness of the audio `beatmap_path` plays; `None` when the beatmap has
+    /// no audio or it can't be decoded.
+    pub fn get(&self, beatmap_path: &Path) -> Result<Option<audio::Loudness>, String> {
+        let content = fs::read_to_string(beatmap_path)
+            .map_err(|e| format!("Failed to read beatmap {}: {}", beatmap_path.display(), e))?;
+        let audio_filename = extract_metadata(&OsuFile::parse(&content)).audio_filename;
+        let Some((audio_path, stamp)) = stamped_audio(beatmap_path, &audio_filename) else {
+            return Ok(None);
+        };
+        if let Some(cached) = self.lock().get(&audio_path).filter(|cached| cached.stamp == stamp) {
+            return Ok(cached.loudness);
+        }
+
+        // Decoded without holding the lock so other tracks aren't held up.
+        let loudness = audio::decode(&audio_path)
+            .map(|decoded| audio::loudness(&decoded))
+            .map_err(|e| log_debug(&format!("Skipping loudness analysis: {}", e)))
+            .ok();
+        self.lock().insert(audio_path, CachedLoudness { stamp, loudness });
+        Ok(loudness)
+    }
+
+    /// Loudness already analysed for `audio_filename`, without decoding anything.
+    pub fn cached(&self, beatmap_path: &Path, audio_filename: &str) -> Option<audio::Loudness> {
+        let (audio_path, stamp) = stamped_audio(beatmap_path, audio_filename)?;
+        self.lock().get(&audio_path).filter(|cached| cached.stamp == stamp)?.loudness
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, CachedLoudness>> {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(feature = "gui")]
+fn stamped_audio(beatmap_path: &Path, audio_filename: &str) -> Option<(PathBuf, (u64, u64))> {
+    if audio_filename.is_empty() {
+        return None;
+    }
+    let audio_path = beatmap_path.with_file_name(audio_filename);
+    let meta = fs::metadata(&audio_path).ok()?;
+    Some((audio_path, file_stamp(&meta)))
+}
+
+/// Async so decoding the audio runs off the main thread.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub async fn load_audio_loudness(cache: State<'_, LoudnessCache>, path: String) -> Result<Option<audio::Loudness>, String> {
+    cache.get(Path::new(&path))
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn load_cached_index(cache: State<'_, IndexCache>, osu_path: String) -> Result<BeatmapIndexResult, String> {
+    let songs_dir = Path::new(&osu_path);
+    let index_path = songs_dir.join(INDEX_FILE_NAME);
+    let beatmaps = cache.get(songs_dir)?.to_vec();
 
     Ok(BeatmapIndexResult {
         index_path: index_path.to_string_lossy().to_string(),
         total: beatmaps.len(),
         duration_ms: 0,
         beatmaps,
+        added: Vec::new(),
+        changed: Vec::new(),
+        deleted: Vec::new(),
+        cancelled: false,
     })
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub async fn select_osu_path(app_handle: tauri::AppHandle) -> Option<String> {
 
@@ -139,7 +492,7 @@ pub async fn select_osu_path(app_handle: tauri::AppHandle) -> Option<String> {
     app_handle
         .dialog()
         .file()
-        .set_title("Select osu! Songs Folder")
+        .set_title("Select osu! Folder or Songs Folder")
         .pick_folder(move |folder| {
             let path = folder.map(|p| p.to_string());
             tx.send(path).ok();
@@ -148,6 +501,7 @@ pub async fn select_osu_path(app_handle: tauri::AppHandle) -> Option<String> {
     rx.recv().unwrap_or(None)
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub async fn select_output_folder(app_handle: tauri::AppHandle) -> Option<String> {
     let (tx, rx) = std::sync::mpsc::channel::<Option<String>>();
@@ -164,11 +518,37 @@ pub async fn select_output_folder(app_handle: tauri::AppHandle) -> Option<String
     rx.recv().unwrap_or(None)
 }
 
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub async fn select_manifest_file(app_handle: tauri::AppHandle, save: bool) -> Option<String> {
+    let (tx, rx) = std::sync::mpsc::channel::<Option<String>>();
+    let dialog = app_handle
+        .dialog()
+        .file()
+        .add_filter("Pack manifest", &["json"]);
+    let send = move |file: Option<tauri_plugin_dialog::FilePath>| {
+        tx.send(file.map(|p| p.to_string())).ok();
+    };
+
+    if save {
+        dialog
+            .set_title("Save pack manifest")
+            .set_file_name("pack.json")
+            .save_file(send);
+    } else {
+        dialog.set_title("Open pack manifest").pick_file(send);
+    }
+
+    rx.recv().unwrap_or(None)
+}
+
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub fn write_app_log(level: String, message: String) -> Result<(), String> {
     persist_log(&level, &message)
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub fn load_osu_files_page(osu_path: String, page: usize, page_size: usize) -> BeatmapPage {
     let mut beatmaps = vec![];
@@ -190,7 +570,7 @@ pub fn load_osu_files_page(osu_path: String, page: usize, page_size: usize) -> B
         return BeatmapPage {
             beatmaps,
             total_dirs: total,
-            total_pages: (total + page_size - 1) / page_size,
+            total_pages: total.div_ceil(page_size),
         };
     }
     let end = (start + page_size).min(total);
@@ -207,143 +587,411 @@ pub fn load_osu_files_page(osu_path: String, page: usize, page_size: usize) -> B
     BeatmapPage {
         beatmaps,
         total_dirs: total,
-        total_pages: (total + page_size - 1) / page_size,
+        total_pages: total.div_ceil(page_size),
     }
 }
 
 
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub fn modify_osu_file(path: String, new_title: String, new_artist: String) -> Result<(), String> {
     let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    let updated = update_osu_file(&content, &new_title, &new_artist);
-    fs::write(&path, updated).map_err(|e| e.to_string())?;
+    let mut osu = OsuFile::parse(&content);
+    osu.set("Metadata", "Title", &new_title);
+    osu.set("Metadata", "Artist", &new_artist);
+    fs::write(&path, osu.to_string()).map_err(|e| e.to_string())?;
     Ok(())
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub fn load_beatmap_details(paths: Vec<String>) -> Result<Vec<BeatmapDetail>, String> {
+pub fn load_beatmap_details(cache: State<'_, LoudnessCache>, paths: Vec<String>) -> Result<Vec<BeatmapDetail>, String> {
+    let mut details = beatmap_details(paths)?;
+    for detail in &mut details {
+        detail.loudness = cache.cached(Path::new(&detail.path), &detail.metadata.audio_filename);
+    }
+    Ok(details)
+}
+
+pub fn beatmap_details(paths: Vec<String>) -> Result<Vec<BeatmapDetail>, String> {
     let mut details = Vec::new();
     for path in paths {
         let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read beatmap {}: {}", path, e))?;
-        let metadata = extract_metadata(&content);
+        let osu = OsuFile::parse(&content);
+        let metadata = extract_metadata(&osu);
+        let timing = extract_timing(&osu, &metadata);
+        let difficulty = calculate_difficulty(&osu, &metadata);
         let id = Path::new(&path)
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or_default()
             .to_string();
-        details.push(BeatmapDetail { id, path, metadata });
+        details.push(BeatmapDetail {
+            id,
+            path,
+            metadata,
+            timing,
+            difficulty,
+            loudness: None,
+        });
     }
     Ok(details)
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub fn create_pack(
-    pack_title: String,
-    pack_artist: String,
-    pack_creator: String,
-    beatmaps: Vec<PackBeatmapInput>,
-    output_dir: Option<String>,
-    include_extra_files: Option<bool>,
-) -> Result<PackCreationResult, String> {
-    if pack_title.trim().is_empty() || pack_artist.trim().is_empty() || pack_creator.trim().is_empty() {
-        return Err("Pack title, artist, and creator cannot be empty".into());
+pub fn create_pack(window: Window, jobs: State<'_, JobRegistry>, manifest: PackManifest) -> u64 {
+    start_pack_job(window, &jobs, manifest)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn validate_pack(manifest: PackManifest) -> PackValidationReport {
+    validate_pack_manifest(&manifest)
+}
+
+/// Checks a pack without writing anything. Errors are problems that would
+/// produce a broken pack; warnings are worth a second look.
+pub fn validate_pack_manifest(manifest: &PackManifest) -> PackValidationReport {
+    let mut report = PackValidationReport::default();
+
+    for (field, value) in [
+        ("title", &manifest.title),
+        ("artist", &manifest.artist),
+        ("creator", &manifest.creator),
+    ] {
+        let value = value.trim();
+        if value.is_empty() {
+            report.error("empty_metadata", None, format!("Pack {} cannot be empty", field));
+        } else if value.chars().any(char::is_control) {
+            report.error(
+                "control_characters",
+                None,
+                format!("Pack {} cannot contain line breaks or other control characters", field),
+            );
+        } else if !value.is_ascii() {
+            report.warning(
+                "non_ascii_metadata",
+                None,
+                format!("Pack {} \"{}\" is not ASCII; osu! expects romanised {} text", field, value, field),
+            );
+        }
     }
-    if beatmaps.is_empty() {
-        return Err("At least one beatmap must be selected".into());
+
+    let title = manifest.title.trim();
+    if !title.is_empty() && !title.chars().any(char::is_control) {
+        let file_name = sanitize_file_name(title);
+        if RESERVED_FILE_NAMES.contains(&file_name.to_ascii_uppercase().as_str()) {
+            report.error(
+                "reserved_title",
+                None,
+                format!("Pack title \"{}\" is a reserved file name on Windows", title),
+            );
+        } else if file_name != title {
+            report.warning(
+                "renamed_title",
+                None,
+                format!("Pack title \"{}\" will be saved as \"{}.osz\"", title, file_name),
+            );
+        }
     }
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| e.to_string())?
-        .as_millis();
-    let pack_folder = std::env::temp_dir().join(format!("osu_pack_{}", timestamp));
-    fs::create_dir_all(&pack_folder).map_err(|e| e.to_string())?;
+    if manifest.beatmaps.is_empty() {
+        report.error("no_beatmaps", None, "At least one beatmap must be selected".into());
+    }
 
-    let mut written_files = 0usize;
+    let rate_changed = manifest.beatmaps.iter().any(|beatmap| beatmap.rate_change().is_some());
+    let wav_audio = match (manifest.normalize_audio, rate_changed) {
+        (true, true) => Some("Normalized and rate-changed"),
+        (true, false) => Some("Normalized"),
+        (false, true) => Some("Rate-changed"),
+        (false, false) => None,
+    };
+    if let Some(kind) = wav_audio {
+        report.warning(
+            "wav_audio_size",
+            None,
+            format!("{} audio is re-encoded as 16-bit WAV, roughly 10x the size of MP3 or OGG", kind),
+        );
+    }
 
-    for (index, beatmap) in beatmaps.iter().enumerate() {
-        let content = fs::read_to_string(&beatmap.path)
-            .map_err(|e| format!("Failed to read beatmap {}: {}", beatmap.path, e))?;
-        let metadata = extract_metadata(&content);
-        let beatmap_dir = Path::new(&beatmap.path)
-            .parent()
-            .ok_or_else(|| format!("Unable to locate beatmap directory: {}", beatmap.path))?;
+    let mut modes = Vec::new();
+    let mut key_counts = Vec::new();
+    let mut versions: Vec<String> = Vec::new();
+    for beatmap in &manifest.beatmaps {
+        let path = Some(beatmap.path.as_str());
+        for (name, value) in [("HP", beatmap.hp_drain_rate), ("OD", beatmap.overall_difficulty)] {
+            if !(0.0..=10.0).contains(&value) {
+                report.error("out_of_range", path, format!("{} {} is outside 0-10", name, value));
+            }
+        }
+        if let Some(rate) = beatmap.rate.filter(|rate| !(MIN_RATE..=MAX_RATE).contains(rate)) {
+            report.error(
+                "out_of_range",
+                path,
+                format!("Rate {} is outside {}-{}", rate, MIN_RATE, MAX_RATE),
+            );
+        }
+        if beatmap.new_version.chars().any(char::is_control) {
+            report.error(
+                "control_characters",
+                path,
+                "Version name cannot contain line breaks or other control characters".into(),
+            );
+        }
 
-        let osu_name = format!("{}.osu", index + 1);
-        let version_value = if beatmap.new_version.trim().is_empty() {
-            if metadata.version.is_empty() {
-                format!("{} - {}", metadata.artist, metadata.title).trim().to_string()
-            } else {
-                metadata.version.clone()
+        let content = match fs::read_to_string(&beatmap.path) {
+            Ok(content) => content,
+            Err(e) => {
+                report.error("unreadable_beatmap", path, format!("Failed to read beatmap: {}", e));
+                report.versions.push(None);
+                continue;
             }
-        } else {
-            beatmap.new_version.trim().to_string()
-        };
-        let audio_ext = extension_with_dot(&metadata.audio_filename);
-        let audio_name = if audio_ext.is_empty() {
-            format!("{}{}", index + 1, ".audio")
-        } else {
-            format!("{}{}", index + 1, audio_ext)
-        };
-        let bg_ext = extension_with_dot(&metadata.background_file);
-        let bg_name = if metadata.background_file.is_empty() {
-            String::new()
-        } else if bg_ext.is_empty() {
-            format!("{}{}", index + 1, ".bg")
-        } else {
-            format!("{}{}", index + 1, bg_ext)
         };
+        let osu = OsuFile::parse(&content);
+        match osu.format_version() {
+            None => report.error(
+                "unsupported_format",
+                path,
+                "Missing \"osu file format\" header".into(),
+            ),
+            Some(version) if version > LATEST_FORMAT_VERSION => report.warning(
+                "unsupported_format",
+                path,
+                format!(
+                    "File format v{} is newer than the v{} osu! stable reads",
+                    version, LATEST_FORMAT_VERSION
+                ),
+            ),
+            Some(_) => {}
+        }
 
-        let mut updated = content;
-        updated = replace_line(&updated, "Title", &pack_title);
-        updated = replace_line(&updated, "TitleUnicode", &pack_title);
-        updated = replace_line(&updated, "Artist", &pack_artist);
-        updated = replace_line(&updated, "ArtistUnicode", &pack_artist);
-        updated = replace_line(&updated, "Creator", &pack_creator);
-        updated = replace_line(&updated, "Version", &version_value);
-        updated = replace_line(&updated, "AudioFilename", &audio_name);
-        updated = replace_line(&updated, "HPDrainRate", &format!("{}", beatmap.hp_drain_rate));
-        updated = replace_line(
-            &updated,
-            "OverallDifficulty",
-            &format!("{}", beatmap.overall_difficulty),
-        );
-        updated = replace_line(&updated, "Source", "");
-        updated = replace_line(&updated, "Tags", "");
-        updated = replace_line(&updated, "BeatmapID", "0");
-        updated = replace_line(&updated, "BeatmapSetID", "-1");
-        if !bg_name.is_empty() {
-            updated = replace_background_reference(&updated, &bg_name);
+        let metadata = extract_metadata(&osu);
+        let beatmap_dir = Path::new(&beatmap.path).parent().unwrap_or(Path::new(""));
+        if metadata.audio_filename.is_empty() {
+            report.error("missing_audio", path, "No AudioFilename set".into());
+        } else if !beatmap_dir.join(&metadata.audio_filename).is_file() {
+            report.error(
+                "missing_audio",
+                path,
+                format!("Audio file \"{}\" not found", metadata.audio_filename),
+            );
+        } else if !audio::is_decodable(Path::new(&metadata.audio_filename)) {
+            if beatmap.rate_change().is_some() {
+                report.error(
+                    "unsupported_audio",
+                    path,
+                    format!(
+                        "Audio \"{}\" can't be rate-changed; supported formats are {}",
+                        metadata.audio_filename,
+                        audio::DECODABLE_EXTENSIONS.join(", ")
+                    ),
+                );
+            } else if manifest.normalize_audio {
+                report.warning(
+                    "unsupported_audio",
+                    path,
+                    format!(
+                        "Audio \"{}\" can't be decoded and will be packed without normalization",
+                        metadata.audio_filename
+                    ),
+                );
+            }
+        }
+        if let Some(columns) = &beatmap.columns {
+            if metadata.mode != 3 {
+                report.error(
+                    "not_mania",
+                    path,
+                    "Column transforms only apply to osu!mania difficulties".into(),
+                );
+            } else if let Err(error) = columns.mapping(metadata.key_count) {
+                report.error("invalid_columns", path, error);
+            }
+        }
+        let keeps_video = manifest.video_policy == VideoPolicy::Include && osu.video().is_some();
+        if beatmap.rate_change().is_some() && (beatmap.include_storyboard || keeps_video) {
+            report.warning(
+                "rate_drops_storyboard",
+                path,
+                "Storyboards and videos are left out of rate-changed difficulties".into(),
+            );
+        }
+        if !metadata.background_file.is_empty() && !beatmap_dir.join(&metadata.background_file).is_file() {
+            report.warning(
+                "missing_background",
+                path,
+                format!("Background \"{}\" not found", metadata.background_file),
+            );
+        }
+        if manifest.video_policy == VideoPolicy::Include {
+            if let Some(video) = osu.video().filter(|video| !beatmap_dir.join(video).is_file()) {
+                report.warning(
+                    "missing_video",
+                    path,
+                    format!("Video \"{}\" not found and will be left out", video),
+                );
+            }
+        }
+        if beatmap.include_storyboard {
+            match storyboard::load(&osu, beatmap_dir) {
+                Ok(storyboard) if storyboard.is_empty() => {
+                    report.warning("no_storyboard", path, "Beatmap has no storyboard to include".into())
+                }
+                Ok(storyboard) => {
+                    let outside = storyboard.outside_files();
+                    if let Some(first) = outside.first() {
+                        report.warning(
+                            "storyboard_outside_folder",
+                            path,
+                            format!(
+                                "{} storyboard file(s) lie outside the beatmap folder and will be left out, e.g. \"{}\"",
+                                outside.len(),
+                                first
+                            ),
+                        );
+                    }
+                    let missing: Vec<String> = storyboard
+                        .files()
+                        .into_iter()
+                        .filter(|file| !beatmap_dir.join(file).is_file())
+                        .collect();
+                    if let Some(first) = missing.first() {
+                        report.warning(
+                            "missing_storyboard_files",
+                            path,
+                            format!("{} storyboard file(s) not found, e.g. \"{}\"", missing.len(), first),
+                        );
+                    }
+                }
+                Err(error) => report.error("unreadable_storyboard", path, error),
+            }
         }
 
-        let osu_path = pack_folder.join(&osu_name);
-        fs::write(&osu_path, updated).map_err(|e| format!("Failed to write file {}: {}", osu_name, e))?;
-        written_files += 1;
+        let version = pack_version_name(beatmap, &metadata);
+        report.versions.push(Some(version.clone()));
+        let osu_file_name = format!(
+            "{} - {} ({}) [{}].osu",
+            manifest.artist, manifest.title, manifest.creator, version
+        );
+        if osu_file_name.len() > MAX_FILE_NAME_LEN {
+            report.warning(
+                "long_file_name",
+                path,
+                format!("\"{}\" is longer than {} bytes", osu_file_name, MAX_FILE_NAME_LEN),
+            );
+        }
+        if versions.iter().any(|other| other.eq_ignore_ascii_case(&version)) {
+            report.warning(
+                "duplicate_version",
+                path,
+                format!("Version name \"{}\" is used by more than one difficulty", version),
+            );
+        } else {
+            versions.push(version);
+        }
 
-        if !metadata.audio_filename.is_empty() {
-            let src_audio = beatmap_dir.join(&metadata.audio_filename);
-            if src_audio.exists() {
-                let dst_audio = pack_folder.join(&audio_name);
-                fs::copy(&src_audio, &dst_audio)
-                    .map_err(|e| format!("Failed to copy audio {} -> {}: {}", src_audio.display(), dst_audio.display(), e))?;
-                written_files += 1;
-            }
+        if !modes.contains(&metadata.mode) {
+            modes.push(metadata.mode);
+        }
+        if metadata.mode == 3 && !key_counts.contains(&metadata.key_count) {
+            key_counts.push(metadata.key_count);
         }
+    }
 
-        if !metadata.background_file.is_empty() && !bg_name.is_empty() {
-            let src_bg = beatmap_dir.join(&metadata.background_file);
-            if src_bg.exists() {
-                let dst_bg = pack_folder.join(&bg_name);
-                fs::copy(&src_bg, &dst_bg)
-                    .map_err(|e| format!("Failed to copy background {} -> {}: {}", src_bg.display(), dst_bg.display(), e))?;
-                written_files += 1;
+    if modes.len() > 1 {
+        let names: Vec<&str> = modes.iter().map(|mode| mode_name(*mode)).collect();
+        report.warning("mixed_modes", None, format!("Pack mixes game modes: {}", names.join(", ")));
+    }
+    if key_counts.len() > 1 {
+        key_counts.sort_unstable();
+        let names: Vec<String> = key_counts.iter().map(|keys| format!("{}K", keys)).collect();
+        report.warning("mixed_key_counts", None, format!("Pack mixes key counts: {}", names.join(", ")));
+    }
+
+    report
+}
+
+fn mode_name(mode: u8) -> &'static str {
+    match mode {
+        0 => "osu!",
+        1 => "taiko",
+        2 => "catch",
+        3 => "mania",
+        _ => "unknown",
+    }
+}
+
+/// The Version a difficulty gets in the pack: the requested name, else its
+/// own Version (or "Artist - Title"), followed by the column transform and
+/// rate suffixes of generated variants.
+fn pack_version_name(beatmap: &PackBeatmapInput, metadata: &BeatmapMetadata) -> String {
+    let mut version = if !beatmap.new_version.trim().is_empty() {
+        beatmap.new_version.trim().to_string()
+    } else if metadata.version.is_empty() {
+        format!("{} - {}", metadata.artist, metadata.title).trim().to_string()
+    } else {
+        metadata.version.clone()
+    };
+    if let Some(columns) = &beatmap.columns {
+        version = format!("{} {}", version, columns.suffix());
+    }
+    if let Some(rate) = beatmap.rate_change() {
+        version = format!("{} {}", version, transform::rate_suffix(rate));
+    }
+    version
+}
+
+#[cfg(feature = "gui")]
+fn start_pack_job(window: Window, jobs: &JobRegistry, manifest: PackManifest) -> u64 {
+    let (job_id, cancel) = jobs.start();
+    std::thread::spawn(move || {
+        let outcome = build_pack(&manifest, &cancel, |progress| {
+            if let Err(err) = window.emit("pack-progress", PackProgressPayload { job_id, progress }) {
+                log_error(&format!("Failed to emit pack progress event: {}", err));
+            }
+        });
+        window.state::<JobRegistry>().finish(job_id);
+        let emitted = match outcome {
+            Ok(result) => window.emit("pack-finished", PackFinishedPayload { job_id, result }),
+            Err(error) => {
+                log_error(&format!("Failed to create pack: {}", error));
+                window.emit("pack-failed", JobFailedPayload { job_id, error })
             }
+        };
+        if let Err(err) = emitted {
+            log_error(&format!("Failed to emit pack result: {}", err));
         }
+    });
+    job_id
+}
+
+/// Builds the .osz described by `manifest`, refusing packs that fail
+/// validation. On failure or cancellation all partial output is removed.
+pub fn build_pack(
+    manifest: &PackManifest,
+    cancel: &CancelToken,
+    on_progress: impl Fn(PackProgress),
+) -> Result<PackCreationResult, String> {
+    let report = validate_pack_manifest(manifest);
+    if !report.errors.is_empty() {
+        let messages: Vec<String> = report
+            .errors
+            .iter()
+            .map(|issue| match &issue.path {
+                Some(path) => format!("{}: {}", path, issue.message),
+                None => issue.message.clone(),
+            })
+            .collect();
+        return Err(messages.join("\n"));
     }
 
-    let sanitized_title = sanitize_file_name(&pack_title);
-    let resolved_parent = output_dir
+    let sanitized_title = sanitize_file_name(&manifest.title);
+    let resolved_parent = manifest
+        .output_dir
+        .as_ref()
+        .filter(|dir| !dir.trim().is_empty())
         .and_then(|dir| {
             let path = PathBuf::from(dir);
             if path.exists() || fs::create_dir_all(&path).is_ok() {
@@ -352,138 +1000,353 @@ pub fn create_pack(
                 None
             }
         })
-        .or_else(|| pack_folder.parent().map(|p| p.to_path_buf()))
-        .unwrap_or_else(|| std::env::temp_dir());
-    let mut osz_file_name = format!("{}.osz", sanitized_title);
-    let mut osz_path = resolved_parent.join(&osz_file_name);
-    if osz_path.exists() {
-        osz_file_name = format!("{}-{}.osz", sanitized_title, timestamp);
-        osz_path = resolved_parent.join(&osz_file_name);
-    }
-    if include_extra_files.unwrap_or(false) {
-        written_files += add_delete_files(&pack_folder, &pack_title, &pack_artist, &pack_creator)?;
-    }
-
-    create_osz_from_folder(&pack_folder, &osz_path)?;
+        .unwrap_or_else(std::env::temp_dir);
+    let (osz_path, folder_path) = unique_pack_paths(&resolved_parent, &sanitized_title, manifest.keep_folder);
+    let folder_path = manifest.keep_folder.then_some(folder_path);
+
+    let written = PackWriter::create(&osz_path, folder_path.as_deref(), manifest.beatmaps.len(), cancel, &on_progress)
+        .and_then(|mut writer| {
+            let md5s = write_pack_files(&mut writer, manifest)?;
+            let file_count = writer.finish()?;
+            Ok((file_count, md5s))
+        });
+    let (file_count, md5s) = match written {
+        Ok(written) => written,
+        Err(error) => {
+            let _ = fs::remove_file(partial_path(&osz_path));
+            if let Some(folder) = &folder_path {
+                let _ = fs::remove_dir_all(folder);
+            }
+            return Err(error);
+        }
+    };
 
     Ok(PackCreationResult {
         osz_path: osz_path.to_string_lossy().to_string(),
-        folder_path: pack_folder.to_string_lossy().to_string(),
-        file_count: written_files,
+        folder_path: folder_path.map(|path| path.to_string_lossy().to_string()),
+        file_count,
+        md5s,
     })
 }
 
-fn update_osu_file(content: &str, title: &str, artist: &str) -> String {
-    let mut updated = content.to_string();
-
-    updated = Regex::new(r"(?m)^Title:[^\n]+")
-        .unwrap()
-        .replace(&updated, format!("Title:{}", title))
-        .to_string();
-
-    updated = Regex::new(r"(?m)^Artist:[^\n]+")
-        .unwrap()
-        .replace(&updated, format!("Artist:{}", artist))
-        .to_string();
+/// Writes every difficulty and its assets into the pack and returns the MD5
+/// of each rewritten .osu file.
+fn write_pack_files(writer: &mut PackWriter<'_>, manifest: &PackManifest) -> Result<Vec<String>, String> {
+    let pack_title = manifest.title.as_str();
+    let pack_artist = manifest.artist.as_str();
+    let pack_creator = manifest.creator.as_str();
+    let mut md5s = Vec::with_capacity(manifest.beatmaps.len());
+    let mut assets = PackAssets::default();
 
-    updated
-}
-
-fn extract_metadata(content: &str) -> BeatmapMetadata {
-    let mut metadata = BeatmapMetadata::default();
-    let mut current_section = String::new();
+    for (index, beatmap) in manifest.beatmaps.iter().enumerate() {
+        let content = fs::read_to_string(&beatmap.path)
+            .map_err(|e| format!("Failed to read beatmap {}: {}", beatmap.path, e))?;
+        let mut osu = OsuFile::parse(&content);
+        let metadata = extract_metadata(&osu);
+        let beatmap_dir = Path::new(&beatmap.path)
+            .parent()
+            .ok_or_else(|| format!("Unable to locate beatmap directory: {}", beatmap.path))?;
 
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with("//") {
-            continue;
+        let rate = beatmap.rate_change();
+        if let Some(rate) = rate {
+            transform::change_rate(&mut osu, rate);
         }
-
-        if trimmed.starts_with('[') && trimmed.ends_with(']') {
-            current_section = trimmed.trim_matches(&['[', ']'][..]).to_string();
-            continue;
+        if let Some(columns) = &beatmap.columns {
+            let mapping = columns.mapping(metadata.key_count)?;
+            transform::remap_columns(&mut osu, &mapping, metadata.key_count);
         }
 
-        if let Some((key, value)) = trimmed.split_once(':') {
-            let key = key.trim();
-            let value = value.trim();
-            match (current_section.as_str(), key) {
-                ("General", "AudioFilename") => metadata.audio_filename = value.to_string(),
-                ("Metadata", "Title") => metadata.title = value.to_string(),
-                ("Metadata", "Artist") => metadata.artist = value.to_string(),
-                ("Metadata", "Creator") => metadata.creator = value.to_string(),
-                ("Metadata", "Version") => metadata.version = value.to_string(),
-                ("Difficulty", "HPDrainRate") => {
-                    metadata.hp_drain_rate = value.parse().unwrap_or(metadata.hp_drain_rate)
-                }
-                ("Difficulty", "OverallDifficulty") => {
-                    metadata.overall_difficulty = value.parse().unwrap_or(metadata.overall_difficulty)
-                }
-                _ => {}
+        let osu_name = format!("{}.osu", index + 1);
+        let version_value = pack_version_name(beatmap, &metadata);
+        let audio_ext = extension_with_dot(&metadata.audio_filename);
+        let mut audio_name = if audio_ext.is_empty() {
+            format!("{}{}", index + 1, ".audio")
+        } else {
+            format!("{}{}", index + 1, audio_ext)
+        };
+        if !metadata.audio_filename.is_empty() {
+            let src_audio = beatmap_dir.join(&metadata.audio_filename);
+            if src_audio.exists() {
+                let normalize = manifest.normalize_audio && audio::is_decodable(&src_audio);
+                audio_name = if rate.is_some() || normalize {
+                    let stem = match rate {
+                        Some(rate) => format!("{} {}", index + 1, transform::rate_suffix(rate)),
+                        None => (index + 1).to_string(),
+                    };
+                    assets.add_processed_audio(writer, &src_audio, rate, normalize, &format!("{}.wav", stem))?
+                } else {
+                    assets.add(writer, &src_audio, &audio_name)?
+                };
             }
-        } else if current_section == "Events" && metadata.background_file.is_empty() {
-            if let Some(start) = trimmed.find('"') {
-                if let Some(end_rel) = trimmed[start + 1..].find('"') {
-                    let end = start + 1 + end_rel;
-                    metadata.background_file = trimmed[start + 1..end].to_string();
-                }
+        }
+        let bg_ext = extension_with_dot(&metadata.background_file);
+        let mut bg_name = if metadata.background_file.is_empty() {
+            String::new()
+        } else if bg_ext.is_empty() {
+            format!("{}{}", index + 1, ".bg")
+        } else {
+            format!("{}{}", index + 1, bg_ext)
+        };
+        if !bg_name.is_empty() {
+            let src_bg = beatmap_dir.join(&metadata.background_file);
+            if src_bg.exists() {
+                bg_name = assets.add(writer, &src_bg, &bg_name)?;
             }
         }
-    }
-
-    metadata
-}
 
-fn replace_line(content: &str, key: &str, value: &str) -> String {
-    let pattern = Regex::new(&format!(r"(?m)^{}:[^\n]*", regex::escape(key))).unwrap();
-    if pattern.is_match(content) {
-        pattern
-            .replace(content, format!("{}:{}", key, value))
-            .to_string()
-    } else {
-        let mut result = content.to_string();
-        if !result.ends_with('\n') {
-            result.push('\n');
+        let video = osu.video().map(|video| beatmap_dir.join(video)).filter(|path| path.is_file());
+        match (manifest.video_policy, video) {
+            (VideoPolicy::Include, Some(src_video)) if rate.is_none() => {
+                let video_name = format!("{}{}", index + 1, extension_with_dot(&src_video.to_string_lossy()));
+                let video_name = assets.add(writer, &src_video, &video_name)?;
+                osu.set_video(&video_name);
+            }
+            _ => osu.strip_videos(),
         }
-        result.push_str(&format!("{}:{}\n", key, value));
-        result
-    }
-}
 
-fn replace_background_reference(content: &str, new_bg: &str) -> String {
-    if new_bg.is_empty() {
-        return content.to_string();
-    }
-
-    let mut result = String::with_capacity(content.len());
-    let mut in_events = false;
-    let mut replaced = false;
-
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with('[') && trimmed.ends_with(']') {
-            in_events = trimmed == "[Events]";
+        assets.add_samples(writer, &mut osu, beatmap_dir)?;
+        if beatmap.include_storyboard && rate.is_none() {
+            assets.add_storyboard(writer, &mut osu, beatmap_dir)?;
+        } else {
+            osu.strip_storyboard();
         }
 
-        if in_events && !replaced && trimmed.contains('"') && !trimmed.starts_with("//") {
-            if let Some(start) = line.find('"') {
-                if let Some(end_rel) = line[start + 1..].find('"') {
-                    let end = start + 1 + end_rel;
-                    let mut new_line = line.to_string();
-                    new_line.replace_range(start + 1..end, new_bg);
-                    result.push_str(&new_line);
-                    result.push('\n');
-                    replaced = true;
-                    continue;
-                }
-            }
+        osu.set("Metadata", "Title", pack_title);
+        osu.set("Metadata", "TitleUnicode", pack_title);
+        osu.set("Metadata", "Artist", pack_artist);
+        osu.set("Metadata", "ArtistUnicode", pack_artist);
+        osu.set("Metadata", "Creator", pack_creator);
+        osu.set("Metadata", "Version", &version_value);
+        osu.set("General", "AudioFilename", &audio_name);
+        osu.set("Difficulty", "HPDrainRate", beatmap.hp_drain_rate);
+        osu.set("Difficulty", "OverallDifficulty", beatmap.overall_difficulty);
+        osu.set("Metadata", "Source", "");
+        osu.set("Metadata", "Tags", "");
+        osu.set("Metadata", "BeatmapID", 0);
+        osu.set("Metadata", "BeatmapSetID", -1);
+        if !bg_name.is_empty() {
+            osu.set_background(&bg_name);
         }
 
-        result.push_str(line);
-        result.push('\n');
+        let osu_content = osu.to_string();
+        md5s.push(md5_hex(osu_content.as_bytes()));
+        writer.write(&osu_name, osu_content.as_bytes(), PackStage::Rewrite)?;
+        writer.finish_beatmap();
     }
 
-    result
+    if manifest.include_extra_files {
+        add_delete_files(writer, pack_title, pack_artist, pack_creator)?;
+    }
+    Ok(md5s)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn save_pack_manifest(path: String, manifest: PackManifest) -> Result<(), String> {
+    manifest::save(Path::new(&path), &manifest)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn load_pack_manifest(path: String) -> Result<PackManifest, String> {
+    manifest::load(Path::new(&path))
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn export_pack_collection(
+    osu_path: String,
+    collection_name: String,
+    md5s: Vec<String>,
+    collection_path: Option<String>,
+) -> Result<CollectionExportResult, String> {
+    let collection_name = collection_name.trim();
+    if collection_name.is_empty() {
+        return Err("Collection name cannot be empty".into());
+    }
+    if md5s.is_empty() {
+        return Err("No beatmaps to add to the collection".into());
+    }
+
+    let collection_path = collection_path
+        .map(PathBuf::from)
+        .map_or_else(|| default_collection_path(Path::new(&osu_path)), Ok)?;
+    let mut database = if collection_path.exists() {
+        collection_db::read_collection_db(&collection_path)?
+    } else {
+        collection_db::CollectionDatabase::default()
+    };
+    let added = database.merge(collection_name, &md5s);
+    collection_db::write_collection_db(&collection_path, &database)?;
+
+    log_debug(&format!(
+        "Added {} beatmaps to collection \"{}\" in {}",
+        added,
+        collection_name,
+        collection_path.display()
+    ));
+
+    Ok(CollectionExportResult {
+        collection_path: collection_path.to_string_lossy().to_string(),
+        added,
+        total: md5s.len(),
+    })
+}
+
+fn extract_metadata(osu: &OsuFile) -> BeatmapMetadata {
+    let text = |section: &str, key: &str| osu.get(section, key).unwrap_or_default().to_string();
+    let mode: u8 = osu.get_parsed("General", "Mode").unwrap_or_default();
+    let key_count = if mode == 3 {
+        osu.get_parsed::<f32>("Difficulty", "CircleSize")
+            .map(|cs| cs.round().max(0.0) as u32)
+            .unwrap_or_default()
+    } else {
+        0
+    };
+
+    let mut note_count = 0usize;
+    let mut long_note_count = 0usize;
+    let mut first_object_time = i32::MAX;
+    let mut last_object_time = i32::MIN;
+    for object in osu.hit_objects() {
+        note_count += 1;
+        if object.is_hold() {
+            long_note_count += 1;
+        }
+        first_object_time = first_object_time.min(object.time);
+        last_object_time = last_object_time.max(object.end_time());
+    }
+    if note_count == 0 {
+        first_object_time = 0;
+        last_object_time = 0;
+    }
+    let ln_percentage = if note_count == 0 {
+        0.0
+    } else {
+        long_note_count as f32 * 100.0 / note_count as f32
+    };
+
+    BeatmapMetadata {
+        title: text("Metadata", "Title"),
+        title_unicode: text("Metadata", "TitleUnicode"),
+        artist: text("Metadata", "Artist"),
+        artist_unicode: text("Metadata", "ArtistUnicode"),
+        creator: text("Metadata", "Creator"),
+        version: text("Metadata", "Version"),
+        source: text("Metadata", "Source"),
+        tags: text("Metadata", "Tags"),
+        audio_filename: text("General", "AudioFilename"),
+        hp_drain_rate: osu.get_parsed("Difficulty", "HPDrainRate").unwrap_or_default(),
+        overall_difficulty: osu.get_parsed("Difficulty", "OverallDifficulty").unwrap_or_default(),
+        background_file: osu.background().unwrap_or_default().to_string(),
+        mode,
+        key_count,
+        note_count,
+        long_note_count,
+        ln_percentage,
+        first_object_time,
+        last_object_time,
+    }
+}
+
+fn apply_bpm_range(timing: &mut BeatmapTiming, points: &[(f64, f64)], last_time: f64) {
+    if points.is_empty() {
+        return;
+    }
+
+    let mut durations: Vec<(f64, f64)> = Vec::new();
+    let mut min_beat_length = f64::MAX;
+    let mut max_beat_length = 0.0f64;
+    for (index, &(time, beat_length)) in points.iter().enumerate() {
+        let rounded = (beat_length * 1000.0).round() / 1000.0;
+        let duration = if time > last_time {
+            0.0
+        } else {
+            min_beat_length = min_beat_length.min(rounded);
+            max_beat_length = max_beat_length.max(rounded);
+            let start = if index == 0 { 0.0 } else { time };
+            let end = points.get(index + 1).map_or(last_time, |next| next.0);
+            (end.min(last_time) - start).max(0.0)
+        };
+        match durations.iter_mut().find(|(length, _)| *length == rounded) {
+            Some(entry) => entry.1 += duration,
+            None => durations.push((rounded, duration)),
+        }
+    }
+    if min_beat_length == f64::MAX {
+        min_beat_length = points[0].1;
+        max_beat_length = points[0].1;
+    }
+    let dominant = durations
+        .iter()
+        .fold(durations[0], |best, &entry| if entry.1 > best.1 { entry } else { best });
+
+    timing.min_bpm = 60_000.0 / max_beat_length;
+    timing.max_bpm = 60_000.0 / min_beat_length;
+    timing.dominant_bpm = 60_000.0 / dominant.0;
+}
+
+fn extract_timing(osu: &OsuFile, metadata: &BeatmapMetadata) -> BeatmapTiming {
+    let points: Vec<(f64, f64)> = osu
+        .timing_points()
+        .filter(|point| point.uninherited && point.beat_length > 0.0)
+        .map(|point| (point.time, point.beat_length))
+        .collect();
+
+    let mut timing = BeatmapTiming::default();
+    apply_bpm_range(&mut timing, &points, f64::from(metadata.last_object_time));
+
+    let break_time: i32 = osu
+        .events()
+        .filter_map(|event| match event {
+            Event::Break { start_time, end_time } => Some((end_time - start_time).max(0)),
+            _ => None,
+        })
+        .sum();
+    let playable = metadata.last_object_time - metadata.first_object_time;
+    timing.drain_time_ms = (playable - break_time).max(0);
+    timing.total_length_ms = metadata.last_object_time.max(0);
+    timing
+}
+
+fn calculate_difficulty(osu: &OsuFile, metadata: &BeatmapMetadata) -> Option<ManiaDifficulty> {
+    if metadata.mode != 3 || metadata.key_count == 0 {
+        return None;
+    }
+    let objects: Vec<HitObject> = osu.hit_objects().cloned().collect();
+    Some(difficulty::calculate(&objects, metadata.key_count, 1.0))
+}
+
+fn read_beatmap(file: &OsuFileEntry) -> Beatmap {
+    let path = &file.path;
+    let mut beatmap = Beatmap {
+        id: file.id.clone(),
+        path: path.to_string_lossy().to_string(),
+        metadata: BeatmapMetadata::default(),
+        timing: BeatmapTiming::default(),
+        star_rating: None,
+        status: None,
+        modified: file.modified,
+        size: file.size,
+        md5: None,
+        last_played: None,
+    };
+    match fs::read(path) {
+        Ok(bytes) => {
+            beatmap.md5 = Some(md5_hex(&bytes));
+            let osu = OsuFile::parse(&String::from_utf8_lossy(&bytes));
+            beatmap.metadata = extract_metadata(&osu);
+            beatmap.timing = extract_timing(&osu, &beatmap.metadata);
+            beatmap.star_rating =
+                calculate_difficulty(&osu, &beatmap.metadata).map(|difficulty| difficulty.star_rating);
+        }
+        Err(e) => log_error(&format!("Failed to read beatmap {}: {}", path.display(), e)),
+    }
+    beatmap
+}
+
+fn md5_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Md5::digest(bytes))
 }
 
 fn sanitize_file_name(input: &str) -> String {
@@ -500,30 +1363,21 @@ fn sanitize_file_name(input: &str) -> String {
 }
 
 fn add_delete_files(
-    pack_folder: &Path,
+    writer: &mut PackWriter<'_>,
     pack_title: &str,
     pack_artist: &str,
     pack_creator: &str,
-) -> Result<usize, String> {
-    let mut updated = DELETE_OSU_TEMPLATE.to_string();
-    updated = replace_line(&updated, "Title", pack_title);
-    updated = replace_line(&updated, "TitleUnicode", pack_title);
-    updated = replace_line(&updated, "Artist", pack_artist);
-    updated = replace_line(&updated, "ArtistUnicode", pack_artist);
-    updated = replace_line(&updated, "Creator", pack_creator);
-
-    let delete_osu_path = pack_folder.join("delete.osu");
-    fs::write(&delete_osu_path, updated).map_err(|e| format!("Failed to write delete.osu: {}", e))?;
-
-    let delete_bg_path = pack_folder.join("delete.jpg");
-    fs::write(&delete_bg_path, DELETE_BG_BYTES)
-        .map_err(|e| format!("Failed to write delete.jpg: {}", e))?;
-
-    let delete_audio_path = pack_folder.join("delete.mp3");
-    fs::write(&delete_audio_path, DELETE_AUDIO_BYTES)
-        .map_err(|e| format!("Failed to write delete.mp3: {}", e))?;
-
-    Ok(3)
+) -> Result<(), String> {
+    let mut osu = OsuFile::parse(DELETE_OSU_TEMPLATE);
+    osu.set("Metadata", "Title", pack_title);
+    osu.set("Metadata", "TitleUnicode", pack_title);
+    osu.set("Metadata", "Artist", pack_artist);
+    osu.set("Metadata", "ArtistUnicode", pack_artist);
+    osu.set("Metadata", "Creator", pack_creator);
+
+    writer.write("delete.osu", osu.to_string().as_bytes(), PackStage::Rewrite)?;
+    writer.write("delete.jpg", DELETE_BG_BYTES, PackStage::Copy)?;
+    writer.write("delete.mp3", DELETE_AUDIO_BYTES, PackStage::Copy)
 }
 
 fn extension_with_dot(file_name: &str) -> String {
@@ -537,38 +1391,339 @@ fn extension_with_dot(file_name: &str) -> String {
         .unwrap_or_default()
 }
 
-fn create_osz_from_folder(folder: &Path, osz_path: &Path) -> Result<(), String> {
-    let file = fs::File::create(osz_path).map_err(|e| format!("Unable to create package file: {}", e))?;
-    let mut zip = zip::ZipWriter::new(file);
-    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+/// Picks `<title>.osz`, then `<title> (2).osz`, `<title> (3).osz`, ... so an
+/// existing pack (or its folder copy, when one is kept) is never overwritten.
+fn unique_pack_paths(parent: &Path, title: &str, keep_folder: bool) -> (PathBuf, PathBuf) {
+    let mut number = 1;
+    loop {
+        let stem = if number == 1 {
+            title.to_string()
+        } else {
+            format!("{} ({})", title, number)
+        };
+        let osz_path = parent.join(format!("{}.osz", stem));
+        let folder_path = parent.join(&stem);
+        let taken = osz_path.exists() || partial_path(&osz_path).exists() || (keep_folder && folder_path.exists());
+        if !taken {
+            return (osz_path, folder_path);
+        }
+        number += 1;
+    }
+}
+
+/// Sibling the .osz is written to before being renamed into place, so osu!
+/// never sees a half-written pack.
+fn partial_path(osz_path: &Path) -> PathBuf {
+    let mut name = osz_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    osz_path.with_file_name(name)
+}
 
-    for entry in fs::read_dir(folder).map_err(|e| format!("Failed to read folder: {}", e))? {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let path = entry.path();
-        if path.is_file() {
-            let name = entry
-                .file_name()
-                .to_string_lossy()
-                .to_string();
-            zip.start_file(name, options)
-                .map_err(|e| format!("Failed to write zip entry: {}", e))?;
-            let mut src = fs::File::open(&path).map_err(|e| e.to_string())?;
-            std::io::copy(&mut src, &mut zip).map_err(|e| e.to_string())?;
+/// Streams pack files into the .osz and, when asked, mirrors them into a
+/// plain folder next to it. Every write reports progress and honours
+/// cancellation.
+struct PackWriter<'a> {
+    zip: zip::ZipWriter<fs::File>,
+    osz_path: PathBuf,
+    folder: Option<PathBuf>,
+    progress: PackProgress,
+    cancel: &'a CancelToken,
+    on_progress: &'a dyn Fn(PackProgress),
+}
+
+impl<'a> PackWriter<'a> {
+    fn create(
+        osz_path: &Path,
+        folder: Option<&Path>,
+        total_beatmaps: usize,
+        cancel: &'a CancelToken,
+        on_progress: &'a dyn Fn(PackProgress),
+    ) -> Result<Self, String> {
+        if let Some(folder) = folder {
+            fs::create_dir_all(folder)
+                .map_err(|e| format!("Failed to create folder {}: {}", folder.display(), e))?;
         }
+        let file = fs::File::create(partial_path(osz_path))
+            .map_err(|e| format!("Unable to create package file: {}", e))?;
+        Ok(PackWriter {
+            zip: zip::ZipWriter::new(file),
+            osz_path: osz_path.to_path_buf(),
+            folder: folder.map(Path::to_path_buf),
+            progress: PackProgress {
+                stage: PackStage::Rewrite,
+                file: String::new(),
+                files_written: 0,
+                bytes_written: 0,
+                beatmaps_done: 0,
+                total_beatmaps,
+            },
+            cancel,
+            on_progress,
+        })
     }
 
-    zip.finish().map_err(|e| e.to_string())?;
-    Ok(())
+    fn check_cancelled(&self) -> Result<(), String> {
+        if self.cancel.is_cancelled() {
+            return Err("Pack creation cancelled".into());
+        }
+        Ok(())
+    }
+
+    fn report(&mut self, stage: PackStage, file: &str) {
+        self.progress.stage = stage;
+        self.progress.file = file.to_string();
+        (self.on_progress)(self.progress.clone());
+    }
+
+    fn write(&mut self, name: &str, bytes: &[u8], stage: PackStage) -> Result<(), String> {
+        self.check_cancelled()?;
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        self.zip
+            .start_file(name, options)
+            .and_then(|_| self.zip.write_all(bytes).map_err(Into::into))
+            .map_err(|e| format!("Failed to write zip entry {}: {}", name, e))?;
+        if let Some(folder) = &self.folder {
+            let path = folder.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create folder {}: {}", parent.display(), e))?;
+            }
+            fs::write(&path, bytes).map_err(|e| format!("Failed to write file {}: {}", path.display(), e))?;
+        }
+        self.progress.files_written += 1;
+        self.progress.bytes_written += bytes.len() as u64;
+        self.report(stage, name);
+        Ok(())
+    }
+
+    fn finish_beatmap(&mut self) {
+        self.progress.beatmaps_done += 1;
+    }
+
+    fn finish(mut self) -> Result<usize, String> {
+        self.check_cancelled()?;
+        let osz_name = self.osz_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        self.report(PackStage::Compress, &osz_name);
+        let file = self.zip.finish().map_err(|e| e.to_string())?;
+        file.sync_all()
+            .map_err(|e| format!("Failed to flush package file: {}", e))?;
+        drop(file);
+        fs::rename(partial_path(&self.osz_path), &self.osz_path)
+            .map_err(|e| format!("Failed to move package into place: {}", e))?;
+        Ok(self.progress.files_written)
+    }
 }
 
-fn collect_folder_beatmaps(folder: &Path, folder_name: &str) -> Vec<Beatmap> {
-    let mut beatmaps = Vec::new();
+/// Files already written to a pack, so difficulties that share audio, images
+/// or hitsound samples store a single copy and point at its name.
+#[derive(Default)]
+struct PackAssets {
+    by_source: HashMap<PathBuf, String>,
+    by_hash: HashMap<String, String>,
+    /// Content hash of every file in the pack, keyed by lowercase name.
+    names: HashMap<String, String>,
+    /// Sample files claimed for each custom sample index.
+    banks: HashMap<i32, BTreeMap<SampleSlot, String>>,
+    /// Pack subfolder holding each source folder's storyboard files.
+    storyboard_folders: HashMap<PathBuf, String>,
+    /// Time-stretched or normalized audio already written, keyed by source,
+    /// rate bits and whether it was normalized.
+    processed_audio: HashMap<(PathBuf, u64, bool), String>,
+}
+
+impl PackAssets {
+    /// Returns the pack file name holding `source`'s content, writing it as
+    /// `name` (or a numbered variant if that name is taken) when no identical
+    /// file is in the pack yet.
+    fn add(&mut self, writer: &mut PackWriter<'_>, source: &Path, name: &str) -> Result<String, String> {
+        if let Some(existing) = self.by_source.get(source) {
+            return Ok(existing.clone());
+        }
+
+        let bytes = fs::read(source).map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        let hash = md5_hex(&bytes);
+        if let Some(existing) = self.by_hash.get(&hash) {
+            let existing = existing.clone();
+            self.by_source.insert(source.to_path_buf(), existing.clone());
+            return Ok(existing);
+        }
+
+        let name = self.free_name(name);
+        self.write(writer, &name, &bytes, hash)?;
+        self.by_source.insert(source.to_path_buf(), name.clone());
+        Ok(name)
+    }
+
+    /// Like `add`, but writes `source` as WAV after time-stretching it to
+    /// `rate` and/or normalizing its loudness.
+    fn add_processed_audio(
+        &mut self,
+        writer: &mut PackWriter<'_>,
+        source: &Path,
+        rate: Option<f64>,
+        normalize: bool,
+        name: &str,
+    ) -> Result<String, String> {
+        let key = (source.to_path_buf(), rate.unwrap_or(1.0).to_bits(), normalize);
+        if let Some(existing) = self.processed_audio.get(&key) {
+            return Ok(existing.clone());
+        }
+
+        writer.check_cancelled()?;
+        let mut processed = audio::decode(source)?;
+        if let Some(rate) = rate {
+            processed = audio::time_stretch(&processed, rate);
+        }
+        if normalize {
+            let loudness = audio::loudness(&processed);
+            let gain = audio::normalization_gain_db(&loudness, NORMALIZE_TARGET_LUFS, NORMALIZE_CEILING_DBFS);
+            audio::apply_gain(&mut processed, gain);
+        }
+        let bytes = audio::encode_wav(&processed)?;
+        let hash = md5_hex(&bytes);
+        let name = match self.by_hash.get(&hash) {
+            Some(existing) => existing.clone(),
+            None => {
+                let name = self.free_name(name);
+                self.write(writer, &name, &bytes, hash)?;
+                name
+            }
+        };
+        self.processed_audio.insert(key, name.clone());
+        Ok(name)
+    }
+
+    /// Copies the custom hitsound samples `osu` uses. osu! finds indexed
+    /// samples by name, so an index whose files differ from another
+    /// difficulty's is moved to a free index and the references rewritten.
+    fn add_samples(&mut self, writer: &mut PackWriter<'_>, osu: &mut OsuFile, beatmap_dir: &Path) -> Result<(), String> {
+        let files = hitsounds::folder_files(beatmap_dir);
+
+        let mut indices = HashMap::new();
+        for index in hitsounds::used_indices(osu) {
+            let mut contents = Vec::new();
+            for (slot, path) in hitsounds::bank_files(&files, index) {
+                let bytes = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                contents.push((slot, md5_hex(&bytes), bytes));
+            }
+            let bank: BTreeMap<SampleSlot, String> =
+                contents.iter().map(|(slot, hash, _)| (slot.clone(), hash.clone())).collect();
+
+            let (target, claimed) = self.claim_sample_index(index, bank);
+            if claimed {
+                for (slot, hash, bytes) in contents {
+                    self.write(writer, &slot.file_name(target), &bytes, hash)?;
+                }
+            }
+            indices.insert(index, target);
+        }
+
+        let mut filenames = HashMap::new();
+        for filename in hitsounds::custom_filenames(osu) {
+            if let Some(path) = files.get(&filename.to_lowercase()) {
+                let name = self.add(writer, path, &filename)?;
+                if name != filename {
+                    filenames.insert(filename, name);
+                }
+            }
+        }
+
+        hitsounds::rewrite_samples(osu, &indices, &filenames);
+        Ok(())
+    }
+
+    /// Inlines the storyboard `osu` shows (the set's .osb plus its own events)
+    /// and copies the files it uses under a subfolder per source folder, so
+    /// storyboards from different sets never overwrite each other's files.
+    fn add_storyboard(&mut self, writer: &mut PackWriter<'_>, osu: &mut OsuFile, beatmap_dir: &Path) -> Result<(), String> {
+        let storyboard = storyboard::load(osu, beatmap_dir)?;
+        osu.strip_storyboard();
+        if storyboard.is_empty() {
+            return Ok(());
+        }
+
+        let next = self.storyboard_folders.len() + 1;
+        let folder = self
+            .storyboard_folders
+            .entry(beatmap_dir.to_path_buf())
+            .or_insert_with(|| format!("storyboard/{}", next))
+            .clone();
+        for file in storyboard.files() {
+            let name = format!("{}/{}", folder, file);
+            let source = beatmap_dir.join(&file);
+            if self.names.contains_key(&name.to_lowercase()) || !source.is_file() {
+                continue;
+            }
+            let bytes = fs::read(&source).map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+            self.write(writer, &name, &bytes, md5_hex(&bytes))?;
+        }
+
+        osu.append_storyboard(storyboard.relocated(&folder));
+        Ok(())
+    }
+
+    /// Picks `preferred` or the lowest index whose pack files are exactly
+    /// `bank`, or that nothing has claimed yet. Returns the index and whether
+    /// it was newly claimed and still needs its files written.
+    fn claim_sample_index(&mut self, preferred: i32, bank: BTreeMap<SampleSlot, String>) -> (i32, bool) {
+        let mut index = preferred;
+        let mut next = 1;
+        loop {
+            match self.banks.get(&index) {
+                Some(existing) if *existing == bank => return (index, false),
+                Some(_) => {}
+                None if self.bank_names_free(index) => {
+                    self.banks.insert(index, bank);
+                    return (index, true);
+                }
+                None => {}
+            }
+            index = next;
+            next += 1;
+        }
+    }
+
+    fn bank_names_free(&self, index: i32) -> bool {
+        hitsounds::sample_slots().all(|slot| !self.names.contains_key(&slot.file_name(index).to_lowercase()))
+    }
+
+    fn free_name(&self, name: &str) -> String {
+        if !self.names.contains_key(&name.to_lowercase()) {
+            return name.to_string();
+        }
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => name.split_at(dot),
+            _ => (name, ""),
+        };
+        (2..)
+            .map(|number| format!("{} ({}){}", stem, number, extension))
+            .find(|candidate| !self.names.contains_key(&candidate.to_lowercase()))
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    fn write(&mut self, writer: &mut PackWriter<'_>, name: &str, bytes: &[u8], hash: String) -> Result<(), String> {
+        writer.write(name, bytes, PackStage::Copy)?;
+        self.names.insert(name.to_lowercase(), hash.clone());
+        self.by_hash.entry(hash).or_insert_with(|| name.to_string());
+        Ok(())
+    }
+}
+
+struct OsuFileEntry {
+    path: PathBuf,
+    id: String,
+    modified: u64,
+    size: u64,
+}
+
+fn list_osu_files(folder: &Path, folder_name: &str) -> Vec<OsuFileEntry> {
+    let mut files = Vec::new();
 
     let inner = match fs::read_dir(folder) {
         Ok(it) => it,
         Err(e) => {
             log_error(&format!("Failed to read folder {}: {}", folder.display(), e));
-            return beatmaps;
+            return files;
         }
     };
 
@@ -583,20 +1738,66 @@ fn collect_folder_beatmaps(folder: &Path, folder_name: &str) -> Vec<Beatmap> {
             continue;
         }
 
-        if let Some(file_stem) = file_path.file_stem().and_then(|s| s.to_str()) {
-            beatmaps.push(Beatmap {
-                id: file_stem.to_string(),
-                path: file_path.to_string_lossy().to_string(),
-            });
-        } else {
+        let Some(file_stem) = file_path.file_stem().and_then(|s| s.to_str()) else {
             log_debug(&format!(
                 "Skipped file in folder {} because file_stem is missing",
                 folder_name
             ));
-        }
+            continue;
+        };
+        let (modified, size) = file_entry
+            .metadata()
+            .map(|meta| file_stamp(&meta))
+            .unwrap_or((0, 0));
+        files.push(OsuFileEntry {
+            id: file_stem.to_string(),
+            path: file_path,
+            modified,
+            size,
+        });
     }
 
-    beatmaps
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+fn file_stamp(meta: &fs::Metadata) -> (u64, u64) {
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+    (modified, meta.len())
+}
+
+#[cfg(feature = "gui")]
+fn collect_folder_beatmaps(folder: &Path, folder_name: &str) -> Vec<Beatmap> {
+    list_osu_files(folder, folder_name)
+        .iter()
+        .map(read_beatmap)
+        .collect()
+}
+
+fn folder_unchanged(indexed: &[Beatmap], files: &[OsuFileEntry]) -> bool {
+    indexed.len() == files.len()
+        && files.iter().all(|file| {
+            let path = file.path.to_string_lossy();
+            indexed.iter().any(|beatmap| {
+                beatmap.path == path
+                    && beatmap.modified == file.modified
+                    && beatmap.size == file.size
+                    && file.modified != 0
+            })
+        })
+}
+
+fn folder_name_of(beatmap: &Beatmap) -> String {
+    Path::new(&beatmap.path)
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
 }
 
 struct FolderEntry {
@@ -614,72 +1815,269 @@ fn read_song_directories(songs_dir: &Path) -> Vec<FolderEntry> {
     };
 
     entries.retain(|entry| entry.path().is_dir());
-    entries.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    entries.sort_by_key(|entry| entry.file_name());
 
-    entries.into_iter().filter_map(|entry| {
+    entries.into_iter().map(|entry| {
         let path = entry.path();
         let name = entry.file_name().to_string_lossy().to_string();
-        Some(FolderEntry { path, name })
+        FolderEntry { path, name }
     }).collect()
 }
 
-fn collect_all_beatmaps(songs_dir: &Path) -> Vec<Beatmap> {
-    let folders = read_song_directories(songs_dir);
-    let mut all = Vec::new();
-    for folder in folders {
-        all.extend(collect_folder_beatmaps(&folder.path, &folder.name));
-    }
-    all
-}
-
-fn emit_progress(window: &Window, folder: &str, scanned: usize) {
+#[cfg(feature = "gui")]
+fn emit_progress(window: &Window, job_id: u64, progress: IndexProgress) {
     if let Err(err) = window.emit(
         "index-progress",
-        IndexProgressPayload {
-            folder: folder.to_string(),
-            scanned,
-        },
+        IndexProgressPayload { job_id, progress },
     ) {
         log_error(&format!("Failed to emit progress event: {}", err));
     }
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub fn search_beatmaps_by_id(osu_path: String, keyword: String) -> Vec<Beatmap> {
+pub fn search_beatmaps_by_id(cache: State<'_, IndexCache>, osu_path: String, keyword: String) -> Vec<Beatmap> {
     let trimmed = keyword.trim().to_lowercase();
     if trimmed.is_empty() {
         return Vec::new();
     }
 
     let songs_dir = Path::new(&osu_path);
-    collect_all_beatmaps(songs_dir)
-        .into_iter()
-        .filter(|beatmap| beatmap.id.to_lowercase().contains(&trimmed))
-        .collect()
+    if let Ok(beatmaps) = cache.get(songs_dir) {
+        return beatmaps
+            .iter()
+            .filter(|beatmap| {
+                beatmap.id.to_lowercase().contains(&trimmed) || folder_name_of(beatmap).to_lowercase().contains(&trimmed)
+            })
+            .cloned()
+            .collect();
+    }
+
+    // Without an index, only the files whose folder or name match are parsed.
+    let mut beatmaps = Vec::new();
+    for folder in read_song_directories(songs_dir) {
+        let folder_matches = folder.name.to_lowercase().contains(&trimmed);
+        beatmaps.extend(
+            list_osu_files(&folder.path, &folder.name)
+                .iter()
+                .filter(|file| folder_matches || file.id.to_lowercase().contains(&trimmed))
+                .map(read_beatmap),
+        );
+    }
+    beatmaps
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub fn build_beatmap_index(window: Window, osu_path: String) -> Result<BeatmapIndexResult, String> {
-    let songs_dir = Path::new(&osu_path);
+pub fn query_beatmaps(
+    cache: State<'_, IndexCache>,
+    osu_path: String,
+    query: String,
+    page: usize,
+    page_size: usize,
+) -> Result<BeatmapQueryPage, String> {
+    let beatmaps = cache.get(Path::new(&osu_path))?;
+    query_page(&beatmaps, &query, page, page_size)
+}
+
+/// One page of the beatmaps matching a filter query.
+pub fn query_page(beatmaps: &[Beatmap], query: &str, page: usize, page_size: usize) -> Result<BeatmapQueryPage, String> {
+    let matches = search::query(beatmaps, query)?;
+    let total = matches.len();
+    if page_size == 0 {
+        return Ok(BeatmapQueryPage {
+            beatmaps: Vec::new(),
+            total_results: total,
+            total_pages: 0,
+        });
+    }
+
+    let start = page.saturating_mul(page_size).min(total);
+    let end = (start + page_size).min(total);
+    Ok(BeatmapQueryPage {
+        beatmaps: matches[start..end].to_vec(),
+        total_results: total,
+        total_pages: total.div_ceil(page_size),
+    })
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn build_beatmap_index(
+    window: Window,
+    jobs: State<'_, JobRegistry>,
+    osu_path: String,
+) -> Result<u64, String> {
+    let songs_dir = PathBuf::from(&osu_path);
     if !songs_dir.exists() {
         return Err("Provided path does not exist".into());
     }
 
+    let (job_id, cancel) = jobs.start();
+    std::thread::spawn(move || {
+        let outcome = build_index(&songs_dir, &cancel, |progress| {
+            emit_progress(&window, job_id, progress)
+        });
+        window.state::<IndexCache>().invalidate(&songs_dir);
+        window.state::<JobRegistry>().finish(job_id);
+        let emitted = match outcome {
+            Ok(result) => window.emit("index-finished", IndexFinishedPayload { job_id, result }),
+            Err(error) => {
+                log_error(&format!("Failed to build beatmap index: {}", error));
+                window.emit("index-failed", JobFailedPayload { job_id, error })
+            }
+        };
+        if let Err(err) = emitted {
+            log_error(&format!("Failed to emit index result: {}", err));
+        }
+    });
+
+    Ok(job_id)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn cancel_job(jobs: State<'_, JobRegistry>, job_id: u64) -> bool {
+    jobs.cancel(job_id)
+}
+
+enum FolderOutcome {
+    Unchanged(Vec<Beatmap>),
+    Changed(Vec<Beatmap>),
+    Added(Vec<Beatmap>),
+}
+
+/// Scans the Songs folder on a pool of worker threads, reusing index entries
+/// for folders whose files are unchanged. When cancelled, folders that were
+/// not reached keep their previous entries.
+pub fn build_index(
+    songs_dir: &Path,
+    cancel: &CancelToken,
+    on_progress: impl Fn(IndexProgress) + Sync,
+) -> Result<BeatmapIndexResult, String> {
     let started = Instant::now();
+    let index_path = songs_dir.join(INDEX_FILE_NAME);
+    let mut indexed: HashMap<String, Vec<Beatmap>> = HashMap::new();
+    for beatmap in read_index(&index_path).unwrap_or_default() {
+        indexed.entry(folder_name_of(&beatmap)).or_default().push(beatmap);
+    }
+
     let folders = read_song_directories(songs_dir);
-    let mut beatmaps = Vec::new();
-    let mut scanned = 0usize;
+    let total_folders = folders.len();
+    let next_folder = AtomicUsize::new(0);
+    let scanned_folders = AtomicUsize::new(0);
+    let scanned = AtomicUsize::new(0);
+    let last_emit = Mutex::new(Instant::now());
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(total_folders.max(1));
+
+    let worker_results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut local = Vec::new();
+                    while !cancel.is_cancelled() {
+                        let position = next_folder.fetch_add(1, Ordering::SeqCst);
+                        let Some(folder) = folders.get(position) else {
+                            break;
+                        };
+                        let files = list_osu_files(&folder.path, &folder.name);
+                        let outcome = match indexed.get(&folder.name) {
+                            Some(previous) if folder_unchanged(previous, &files) => {
+                                FolderOutcome::Unchanged(previous.clone())
+                            }
+                            Some(_) => FolderOutcome::Changed(files.iter().map(read_beatmap).collect()),
+                            None if files.is_empty() => FolderOutcome::Unchanged(Vec::new()),
+                            None => FolderOutcome::Added(files.iter().map(read_beatmap).collect()),
+                        };
+                        let count = match &outcome {
+                            FolderOutcome::Unchanged(maps)
+                            | FolderOutcome::Changed(maps)
+                            | FolderOutcome::Added(maps) => maps.len(),
+                        };
+                        local.push((position, outcome));
+
+                        let done = scanned_folders.fetch_add(1, Ordering::SeqCst) + 1;
+                        let total_scanned = scanned.fetch_add(count, Ordering::SeqCst) + count;
+                        let mut last = last_emit.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                        if last.elapsed().as_millis() >= PROGRESS_INTERVAL_MS || done == total_folders {
+                            *last = Instant::now();
+                            let elapsed = started.elapsed().as_millis() as u64;
+                            let remaining = (total_folders - done) as u64;
+                            on_progress(IndexProgress {
+                                folder: folder.name.clone(),
+                                scanned: total_scanned,
+                                scanned_folders: done,
+                                total_folders,
+                                eta_ms: Some(elapsed * remaining / done as u64),
+                            });
+                        }
+                    }
+                    local
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join()).collect()
+    });
+    // A worker that died took its folders with it; writing the index now
+    // would report all of them as deleted.
+    let mut outcomes: Vec<(usize, FolderOutcome)> = Vec::new();
+    for result in worker_results {
+        outcomes.extend(result.map_err(|_| "An index worker crashed; the index was left unchanged".to_string())?);
+    }
+    outcomes.sort_by_key(|(position, _)| *position);
 
-    for folder in folders {
-        emit_progress(&window, &folder.name, scanned);
-        let folder_maps = collect_folder_beatmaps(&folder.path, &folder.name);
-        scanned += folder_maps.len();
-        beatmaps.extend(folder_maps);
-        emit_progress(&window, &folder.name, scanned);
+    let cancelled = cancel.is_cancelled();
+    let mut reached = vec![false; total_folders];
+    let mut beatmaps = Vec::new();
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for (position, outcome) in outcomes {
+        reached[position] = true;
+        let name = &folders[position].name;
+        match outcome {
+            FolderOutcome::Unchanged(maps) => beatmaps.extend(maps),
+            FolderOutcome::Changed(maps) => {
+                changed.push(name.clone());
+                beatmaps.extend(maps);
+            }
+            FolderOutcome::Added(maps) => {
+                added.push(name.clone());
+                beatmaps.extend(maps);
+            }
+        }
+        indexed.remove(name);
+    }
+    if cancelled {
+        for (position, folder) in folders.iter().enumerate() {
+            if !reached[position] {
+                if let Some(previous) = indexed.remove(&folder.name) {
+                    beatmaps.extend(previous);
+                }
+            }
+        }
     }
 
-    emit_progress(&window, "Finished", scanned);
-    let index_path = songs_dir.join(INDEX_FILE_NAME);
+    let mut deleted: Vec<String> = indexed.into_keys().collect();
+    deleted.sort();
+    log_debug(&format!(
+        "Index rebuild{}: {} added, {} changed, {} deleted folders",
+        if cancelled { " (cancelled)" } else { "" },
+        added.len(),
+        changed.len(),
+        deleted.len()
+    ));
+
+    let total_scanned = scanned.load(Ordering::SeqCst);
+    on_progress(IndexProgress {
+        folder: "Finished".to_string(),
+        scanned: total_scanned,
+        scanned_folders: scanned_folders.load(Ordering::SeqCst),
+        total_folders,
+        eta_ms: Some(0),
+    });
 
     let json = serde_json::to_string_pretty(&beatmaps).map_err(|e| e.to_string())?;
     fs::write(&index_path, json).map_err(|e| e.to_string())?;
@@ -690,5 +2088,344 @@ pub fn build_beatmap_index(window: Window, osu_path: String) -> Result<BeatmapIn
         total: beatmaps.len(),
         duration_ms,
         beatmaps,
+        added,
+        changed,
+        deleted,
+        cancelled,
     })
 }
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn import_osu_db(cache: State<'_, IndexCache>, osu_path: String) -> Result<OsuDbImportResult, String> {
+    let result = import_osu_db_index(Path::new(&osu_path))?;
+    cache.invalidate(Path::new(&result.songs_path));
+    Ok(result)
+}
+
+/// Fills the beatmap index from osu! stable's `osu!.db` instead of scanning
+/// every folder. `install_dir` is the osu! folder that holds the database.
+pub fn import_osu_db_index(install_dir: &Path) -> Result<OsuDbImportResult, String> {
+    let db_path = install_dir.join(OSU_DB_FILE_NAME);
+    if !db_path.exists() {
+        return Err(format!("{} not found in {}", OSU_DB_FILE_NAME, install_dir.display()));
+    }
+
+    let started = Instant::now();
+    let database = osu_db::read_osu_db(&db_path)?;
+    let songs_dir = find_songs_dir(install_dir);
+    let mut beatmaps = Vec::with_capacity(database.beatmaps.len());
+    let mut missing = 0usize;
+
+    for entry in &database.beatmaps {
+        let path = songs_dir.join(&entry.folder_name).join(&entry.file_name);
+        let Ok(meta) = fs::metadata(&path) else {
+            missing += 1;
+            continue;
+        };
+        let (modified, size) = file_stamp(&meta);
+        beatmaps.push(beatmap_from_db(entry, &path, modified, size));
+    }
+
+    log_debug(&format!(
+        "Imported {} beatmaps from {} (version {}, {} missing on disk)",
+        beatmaps.len(),
+        db_path.display(),
+        database.version,
+        missing
+    ));
+
+    let index_path = songs_dir.join(INDEX_FILE_NAME);
+    let json = serde_json::to_string_pretty(&beatmaps).map_err(|e| e.to_string())?;
+    fs::write(&index_path, json).map_err(|e| e.to_string())?;
+
+    Ok(OsuDbImportResult {
+        songs_path: songs_dir.to_string_lossy().to_string(),
+        player_name: database.player_name,
+        index: BeatmapIndexResult {
+            index_path: index_path.to_string_lossy().to_string(),
+            total: beatmaps.len(),
+            duration_ms: started.elapsed().as_millis(),
+            beatmaps,
+            added: Vec::new(),
+            changed: Vec::new(),
+            deleted: Vec::new(),
+            cancelled: false,
+        },
+    })
+}
+
+fn beatmap_from_db(entry: &OsuDbBeatmap, path: &Path, modified: u64, size: u64) -> Beatmap {
+    let mode = entry.mode;
+    let is_mania = mode == 3;
+    let note_count =
+        usize::from(entry.hit_circles) + usize::from(entry.sliders) + usize::from(entry.spinners);
+    let long_note_count = if is_mania { usize::from(entry.sliders) } else { 0 };
+    let metadata = BeatmapMetadata {
+        title: entry.title.clone(),
+        title_unicode: entry.title_unicode.clone(),
+        artist: entry.artist.clone(),
+        artist_unicode: entry.artist_unicode.clone(),
+        creator: entry.creator.clone(),
+        version: entry.version.clone(),
+        source: entry.source.clone(),
+        tags: entry.tags.clone(),
+        audio_filename: entry.audio_filename.clone(),
+        hp_drain_rate: entry.hp_drain_rate,
+        overall_difficulty: entry.overall_difficulty,
+        background_file: String::new(),
+        mode,
+        key_count: if is_mania { entry.circle_size.round().max(0.0) as u32 } else { 0 },
+        note_count,
+        long_note_count,
+        ln_percentage: if note_count == 0 {
+            0.0
+        } else {
+            long_note_count as f32 * 100.0 / note_count as f32
+        },
+        first_object_time: 0,
+        last_object_time: entry.total_time_ms,
+    };
+
+    let points: Vec<(f64, f64)> = entry
+        .timing_points
+        .iter()
+        .filter(|(_, beat_length, uninherited)| *uninherited && *beat_length > 0.0)
+        .map(|(time, beat_length, _)| (*time, *beat_length))
+        .collect();
+    let mut timing = BeatmapTiming {
+        drain_time_ms: entry.drain_time_s.saturating_mul(1000),
+        total_length_ms: entry.total_time_ms,
+        ..BeatmapTiming::default()
+    };
+    apply_bpm_range(&mut timing, &points, f64::from(entry.total_time_ms));
+
+    Beatmap {
+        id: path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        metadata,
+        timing,
+        star_rating: entry.star_ratings.get(usize::from(mode)).copied().flatten(),
+        status: Some(osu_db::ranked_status_name(entry.ranked_status).to_string()),
+        modified,
+        size,
+        md5: Some(entry.md5.clone()).filter(|md5| !md5.is_empty()),
+        last_played: entry.last_played,
+    }
+}
+
+fn find_songs_dir(install_dir: &Path) -> PathBuf {
+    let configured = fs::read_dir(install_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            name.starts_with("osu!.") && name.ends_with(".cfg")
+        })
+        .find_map(|cfg| {
+            let content = fs::read_to_string(&cfg).ok()?;
+            content.lines().find_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                (key.trim() == "BeatmapDirectory").then(|| value.trim().to_string())
+            })
+        })
+        .filter(|value| !value.is_empty());
+
+    match configured {
+        Some(dir) => install_dir.join(dir),
+        None => install_dir.join("Songs"),
+    }
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn load_collections(
+    cache: State<'_, IndexCache>,
+    osu_path: String,
+    collection_path: Option<String>,
+) -> Result<Vec<CollectionEntry>, String> {
+    let songs_dir = Path::new(&osu_path);
+    let collection_path = collection_path
+        .map(PathBuf::from)
+        .map_or_else(|| default_collection_path(songs_dir), Ok)?;
+    let database = collection_db::read_collection_db(&collection_path)?;
+    let beatmaps = cache.get(songs_dir)?;
+
+    let by_hash: HashMap<&str, &Beatmap> = beatmaps
+        .iter()
+        .filter_map(|beatmap| beatmap.md5.as_deref().map(|md5| (md5, beatmap)))
+        .collect();
+
+    log_debug(&format!(
+        "Loaded {} collections from {} (version {})",
+        database.collections.len(),
+        collection_path.display(),
+        database.version
+    ));
+
+    Ok(database
+        .collections
+        .into_iter()
+        .map(|collection| {
+            let mut resolved = Vec::new();
+            let mut missing = Vec::new();
+            for hash in collection.hashes {
+                match by_hash.get(hash.as_str()) {
+                    Some(beatmap) => resolved.push((*beatmap).clone()),
+                    None => missing.push(hash),
+                }
+            }
+            CollectionEntry {
+                name: collection.name,
+                beatmaps: resolved,
+                missing,
+            }
+        })
+        .collect())
+}
+
+/// Finds collection.db next to `osu_path` or in its parent. A missing file is
+/// only created inside a folder that is recognisably an osu! install, so a
+/// stray Songs copy never gets a collection.db osu! will not read.
+#[cfg(feature = "gui")]
+fn default_collection_path(osu_path: &Path) -> Result<PathBuf, String> {
+    let candidates: Vec<&Path> = std::iter::once(osu_path).chain(osu_path.parent()).collect();
+    if let Some(existing) = candidates
+        .iter()
+        .map(|dir| dir.join(COLLECTION_DB_FILE_NAME))
+        .find(|path| path.is_file())
+    {
+        return Ok(existing);
+    }
+    candidates
+        .iter()
+        .find(|dir| dir.join(OSU_EXE_FILE_NAME).is_file() || dir.join(OSU_DB_FILE_NAME).is_file())
+        .map(|install_dir| install_dir.join(COLLECTION_DB_FILE_NAME))
+        .ok_or_else(|| {
+            format!(
+                "No osu! install found at {}; choose collection.db explicitly",
+                osu_path.display()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timing_reports_bpm_range_dominant_bpm_and_drain() {
+        let osu = OsuFile::parse(concat!(
+            "[General]\nMode: 3\n\n[Difficulty]\nCircleSize:4\n\n",
+            "[Events]\n2,4000,6000\n\n",
+            "[TimingPoints]\n0,500,4,1,0,100,1,0\n2000,-100,4,1,0,100,0,0\n",
+            "3000,250,4,1,0,100,1,0\n4000,600,4,1,0,100,1,0\n20000,100,4,1,0,100,1,0\n\n",
+            "[HitObjects]\n64,192,1000,1,0,0:0:0:0:\n192,192,10000,1,0,0:0:0:0:\n",
+        ));
+        let metadata = extract_metadata(&osu);
+        let timing = extract_timing(&osu, &metadata);
+
+        // 120 BPM for 3s, 240 BPM for 1s, 100 BPM for 6s; the red line after
+        // the last note does not count.
+        assert!((timing.min_bpm - 100.0).abs() < 1e-9);
+        assert!((timing.max_bpm - 240.0).abs() < 1e-9);
+        assert!((timing.dominant_bpm - 100.0).abs() < 1e-9);
+        assert_eq!(timing.drain_time_ms, 9000 - 2000);
+        assert_eq!(timing.total_length_ms, 10000);
+    }
+
+    #[test]
+    fn control_characters_in_names_are_errors() {
+        let manifest: PackManifest = serde_json::from_value(serde_json::json!({
+            "title": "Pack\n[Events]",
+            "artist": "Someone",
+            "creator": "M\te",
+            "beatmaps": [{
+                "path": "missing.osu",
+                "new_version": "Hard\r\nMode: 0",
+                "hp_drain_rate": 5.0,
+                "overall_difficulty": 8.0
+            }]
+        }))
+        .unwrap();
+
+        let report = validate_pack_manifest(&manifest);
+        let paths: Vec<Option<&str>> = report
+            .errors
+            .iter()
+            .filter(|issue| issue.code == "control_characters")
+            .map(|issue| issue.path.as_deref())
+            .collect();
+        assert_eq!(paths, [None, None, Some("missing.osu")]);
+        assert!(report.warnings.iter().all(|issue| issue.code != "renamed_title"));
+    }
+
+    #[test]
+    fn rate_changes_warn_that_audio_is_written_as_wav() {
+        let warning = |rate: f64, normalize_audio: bool| {
+            let manifest: PackManifest = serde_json::from_value(serde_json::json!({
+                "title": "Pack",
+                "artist": "Someone",
+                "creator": "Me",
+                "normalize_audio": normalize_audio,
+                "beatmaps": [{
+                    "path": "missing.osu",
+                    "new_version": "",
+                    "hp_drain_rate": 5.0,
+                    "overall_difficulty": 8.0,
+                    "rate": rate
+                }]
+            }))
+            .unwrap();
+            validate_pack_manifest(&manifest)
+                .warnings
+                .into_iter()
+                .find(|issue| issue.code == "wav_audio_size")
+                .map(|issue| issue.message)
+        };
+
+        assert!(warning(1.0, false).is_none());
+        assert!(warning(1.2, false).unwrap().starts_with("Rate-changed audio"));
+        assert!(warning(1.0, true).unwrap().starts_with("Normalized audio"));
+        assert!(warning(1.2, true).unwrap().starts_with("Normalized and rate-changed audio"));
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn collection_path_needs_an_existing_db_or_osu_install() {
+        let root = std::env::temp_dir().join(format!("collection-path-test-{}", std::process::id()));
+        let songs = root.join("Songs");
+        fs::create_dir_all(&songs).unwrap();
+
+        let stray = default_collection_path(&songs);
+        fs::write(root.join(OSU_EXE_FILE_NAME), b"").unwrap();
+        let install = default_collection_path(&songs);
+        fs::write(songs.join(COLLECTION_DB_FILE_NAME), b"").unwrap();
+        let existing = default_collection_path(&songs);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(stray.is_err());
+        assert_eq!(install.unwrap(), root.join(COLLECTION_DB_FILE_NAME));
+        assert_eq!(existing.unwrap(), songs.join(COLLECTION_DB_FILE_NAME));
+    }
+
+    #[test]
+    fn sample_banks_share_identical_indices_and_move_conflicting_ones() {
+        let slots: Vec<SampleSlot> = hitsounds::sample_slots().take(2).collect();
+        let bank = |hash: &str| BTreeMap::from([(slots[0].clone(), hash.to_string())]);
+        let mut assets = PackAssets::default();
+
+        assert_eq!(assets.claim_sample_index(2, bank("a")), (2, true));
+        assert_eq!(assets.claim_sample_index(2, bank("a")), (2, false));
+        assert_eq!(assets.claim_sample_index(2, bank("b")), (1, true));
+
+        // A file already named like index 3's samples keeps that index free.
+        assets.names.insert(slots[1].file_name(3), "other".into());
+        assert_eq!(assets.claim_sample_index(3, bank("c")), (4, true));
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::osu_file::OsuFile;
+
+const SAMPLE_SETS: [&str; 3] = ["normal", "soft", "drum"];
+const SAMPLE_KINDS: [&str; 7] = [
+    "hitnormal",
+    "hitwhistle",
+    "hitfinish",
+    "hitclap",
+    "slidertick",
+    "sliderslide",
+    "sliderwhistle",
+];
+const SAMPLE_EXTENSIONS: [&str; 3] = ["wav", "ogg", "mp3"];
+
+/// One sample osu! may look up for a custom index, e.g. `soft-hitclap` + `.wav`
+/// becomes `soft-hitclap.wav` for index 1 and `soft-hitclap3.wav` for index 3.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SampleSlot {
+    stem: String,
+    extension: &'static str,
+}
+
+impl SampleSlot {
+    pub fn file_name(&self, index: i32) -> String {
+        if index == 1 {
+            format!("{}.{}", self.stem, self.extension)
+        } else {
+            format!("{}{}.{}", self.stem, index, self.extension)
+        }
+    }
+}
+
+pub fn sample_slots() -> impl Iterator<Item = SampleSlot> {
+    SAMPLE_SETS.iter().flat_map(|set| {
+        SAMPLE_KINDS.iter().flat_map(move |kind| {
+            SAMPLE_EXTENSIONS.iter().map(move |extension| SampleSlot {
+                stem: format!("{}-{}", set, kind),
+                extension,
+            })
+        })
+    })
+}
+
+/// Files directly inside `dir`, keyed by lowercase name because osu! matches
+/// sample names case-insensitively.
+pub fn folder_files(dir: &Path) -> HashMap<String, PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_lowercase();
+            Some((name, path))
+        })
+        .collect()
+}
+
+/// Custom sample indices (1 and up) referenced by timing points or hit objects.
+pub fn used_indices(osu: &OsuFile) -> BTreeSet<i32> {
+    osu.timing_points()
+        .map(|point| point.sample_index)
+        .chain(osu.hit_objects().map(|object| object.sample_index()))
+        .filter(|index| *index > 0)
+        .collect()
+}
+
+/// Sample files hit objects name directly in their hit sample.
+pub fn custom_filenames(osu: &OsuFile) -> BTreeSet<String> {
+    osu.hit_objects()
+        .filter_map(|object| object.sample_filename())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Files in the beatmap folder osu! would play for custom sample `index`.
+pub fn bank_files(files: &HashMap<String, PathBuf>, index: i32) -> Vec<(SampleSlot, PathBuf)> {
+    sample_slots()
+        .filter_map(|slot| {
+            let path = files.get(&slot.file_name(index).to_lowercase())?;
+            Some((slot, path.clone()))
+        })
+        .collect()
+}
+
+/// Points timing points and hit objects at renumbered sample indices and
+/// renamed custom sample files.
+pub fn rewrite_samples(osu: &mut OsuFile, indices: &HashMap<i32, i32>, filenames: &HashMap<String, String>) {
+    for point in osu.timing_points_mut() {
+        if let Some(&index) = indices.get(&point.sample_index) {
+            point.sample_index = index;
+        }
+    }
+    for object in osu.hit_objects_mut() {
+        if let Some(&index) = indices.get(&object.sample_index()) {
+            object.set_sample_index(index);
+        }
+        let renamed = object.sample_filename().and_then(|name| filenames.get(name)).cloned();
+        if let Some(name) = renamed {
+            object.set_sample_filename(&name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHART: &str = concat!(
+        "osu file format v14\n",
+        "\n",
+        "[TimingPoints]\n",
+        "0,500,4,2,1,60,1,0\n",
+        "1000,-100,4,2,3,60,0,0\n",
+        "\n",
+        "[HitObjects]\n",
+        "64,192,0,1,0,0:0:0:0:\n",
+        "192,192,500,1,0,0:0:3:0:\n",
+        "320,192,1000,1,0,0:0:0:70:clap.wav\n",
+    );
+
+    #[test]
+    fn slot_names_leave_out_index_one() {
+        let slot = sample_slots().next().unwrap();
+        assert_eq!(slot.file_name(1), "normal-hitnormal.wav");
+        assert_eq!(slot.file_name(3), "normal-hitnormal3.wav");
+    }
+
+    #[test]
+    fn finds_used_indices_and_custom_files() {
+        let osu = OsuFile::parse(CHART);
+        assert_eq!(used_indices(&osu).into_iter().collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(custom_filenames(&osu).into_iter().collect::<Vec<_>>(), ["clap.wav"]);
+
+        let files = HashMap::from([
+            ("soft-hitclap3.ogg".to_string(), PathBuf::from("Soft-HitClap3.ogg")),
+            ("soft-hitclap.ogg".to_string(), PathBuf::from("soft-hitclap.ogg")),
+        ]);
+        let bank: Vec<_> = bank_files(&files, 3).into_iter().map(|(slot, path)| (slot.file_name(3), path)).collect();
+        assert_eq!(bank, [("soft-hitclap3.ogg".to_string(), PathBuf::from("Soft-HitClap3.ogg"))]);
+    }
+
+    #[test]
+    fn rewrite_remaps_indices_and_renames_files() {
+        let mut osu = OsuFile::parse(CHART);
+        let indices = HashMap::from([(1, 4), (3, 5)]);
+        let filenames = HashMap::from([("clap.wav".to_string(), "clap (2).wav".to_string())]);
+        rewrite_samples(&mut osu, &indices, &filenames);
+
+        let written = osu.to_string();
+        assert!(written.contains("0,500,4,2,4,60,1,0\n"), "{}", written);
+        assert!(written.contains("1000,-100,4,2,5,60,0,0\n"), "{}", written);
+        // Objects following the timing point (index 0) are left alone.
+        assert!(written.contains("64,192,0,1,0,0:0:0:0:\n"), "{}", written);
+        assert!(written.contains("192,192,500,1,0,0:0:5:0:\n"), "{}", written);
+        assert!(written.contains("320,192,1000,1,0,0:0:0:70:clap (2).wav\n"), "{}", written);
+    }
+}
@@ -0,0 +1,60 @@
+#[cfg(feature = "gui")]
+use std::collections::HashMap;
+#[cfg(feature = "gui")]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "gui")]
+use std::sync::Mutex;
+
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    #[cfg(feature = "gui")]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Background jobs started by commands, keyed by the ID handed back to the UI.
+#[cfg(feature = "gui")]
+#[derive(Default)]
+pub struct JobRegistry {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, CancelToken>>,
+}
+
+#[cfg(feature = "gui")]
+impl JobRegistry {
+    pub fn start(&self) -> (u64, CancelToken) {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let token = CancelToken::default();
+        self.jobs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(id, token.clone());
+        (id, token)
+    }
+
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get(&id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, id: u64) {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&id);
+    }
+}
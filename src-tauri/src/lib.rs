@@ -1,21 +1,49 @@
+pub mod cli;
+mod audio;
+#[cfg(feature = "gui")]
+mod collection_db;
 mod commands;
+mod difficulty;
+mod hitsounds;
+mod jobs;
+mod manifest;
+mod osu_db;
+mod osu_file;
+mod search;
+mod storyboard;
+mod transform;
+#[cfg(feature = "gui")]
 use commands::*;
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(jobs::JobRegistry::default())
+        .manage(IndexCache::default())
+        .manage(LoudnessCache::default())
         .invoke_handler(tauri::generate_handler![
             select_osu_path,
             select_output_folder,
+            select_manifest_file,
             load_osu_files_page,
             modify_osu_file,
             search_beatmaps_by_id,
+            query_beatmaps,
             build_beatmap_index,
+            cancel_job,
             load_cached_index,
+            import_osu_db,
+            load_collections,
             load_beatmap_details,
+            load_audio_loudness,
             create_pack,
+            validate_pack,
+            save_pack_manifest,
+            load_pack_manifest,
+            export_pack_collection,
             write_app_log
         ])
         .run(tauri::generate_context!())
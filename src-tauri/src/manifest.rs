@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{PackBeatmapInput, VideoPolicy};
+
+/// Bumped whenever a field changes meaning; older manifests are upgraded on load.
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PackManifest {
+    /// Manifests written before versioning (the first CLI format) count as version 1.
+    #[serde(default = "first_version")]
+    pub version: u32,
+    pub title: String,
+    pub artist: String,
+    pub creator: String,
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub include_extra_files: bool,
+    #[serde(default)]
+    pub keep_folder: bool,
+    #[serde(default)]
+    pub video_policy: VideoPolicy,
+    #[serde(default)]
+    pub normalize_audio: bool,
+    pub beatmaps: Vec<PackBeatmapInput>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+/// Reads a manifest, resolving relative beatmap and output paths against the
+/// manifest's own folder so a pack can be rebuilt from anywhere.
+pub fn load(path: &Path) -> Result<PackManifest, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut manifest: PackManifest =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid manifest {}: {}", path.display(), e))?;
+    if manifest.version > MANIFEST_VERSION {
+        return Err(format!(
+            "Manifest {} uses format version {}, but this build only supports up to {}",
+            path.display(),
+            manifest.version,
+            MANIFEST_VERSION
+        ));
+    }
+    manifest.version = MANIFEST_VERSION;
+
+    if let Some(base) = path.parent() {
+        let resolve = |value: &str| base.join(value).to_string_lossy().to_string();
+        for beatmap in manifest.beatmaps.iter_mut() {
+            if Path::new(&beatmap.path).is_relative() {
+                beatmap.path = resolve(&beatmap.path);
+            }
+        }
+        if let Some(dir) = manifest.output_dir.as_mut() {
+            if !dir.is_empty() && Path::new(dir.as_str()).is_relative() {
+                *dir = resolve(dir);
+            }
+        }
+    }
+    Ok(manifest)
+}
+
+pub fn save(path: &Path, manifest: &PackManifest) -> Result<(), String> {
+    let manifest = PackManifest {
+        version: MANIFEST_VERSION,
+        ..manifest.clone()
+    };
+    let content = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,455 @@
+use std::fs;
+use std::path::Path;
+
+const TICKS_AT_UNIX_EPOCH: i64 = 621_355_968_000_000_000;
+const VERSION_FLOAT_DIFFICULTY: i32 = 20140609;
+const VERSION_NO_ENTRY_SIZE: i32 = 20191106;
+const VERSION_FLOAT_STAR_RATING: i32 = 20250107;
+/// Lower bound on the size of a beatmap entry (all strings empty, no timing
+/// points) and the exact size of a timing point.
+const MIN_BEATMAP_ENTRY_SIZE: usize = 100;
+const TIMING_POINT_SIZE: usize = 17;
+
+pub struct OsuDatabase {
+    pub version: i32,
+    pub player_name: String,
+    pub beatmaps: Vec<OsuDbBeatmap>,
+}
+
+pub struct OsuDbBeatmap {
+    pub artist: String,
+    pub artist_unicode: String,
+    pub title: String,
+    pub title_unicode: String,
+    pub creator: String,
+    pub version: String,
+    pub audio_filename: String,
+    pub md5: String,
+    pub file_name: String,
+    pub ranked_status: u8,
+    pub hit_circles: u16,
+    pub sliders: u16,
+    pub spinners: u16,
+    pub circle_size: f32,
+    pub hp_drain_rate: f32,
+    pub overall_difficulty: f32,
+    /// No-mod star ratings cached by osu! for standard, taiko, catch and mania.
+    pub star_ratings: [Option<f64>; 4],
+    pub drain_time_s: i32,
+    pub total_time_ms: i32,
+    pub timing_points: Vec<(f64, f64, bool)>,
+    pub mode: u8,
+    pub source: String,
+    pub tags: String,
+    /// Unix time in milliseconds, `None` when the map was never played.
+    pub last_played: Option<i64>,
+    pub folder_name: String,
+}
+
+/// Little-endian reader for the .NET-style primitives osu! stable writes to
+/// its database files.
+pub struct DbReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> DbReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        DbReader { bytes, position: 0 }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self.position + count;
+        if end > self.bytes.len() {
+            return Err(format!("Unexpected end of database at byte {}", self.position));
+        }
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    /// How many of `count` entries of at least `min_size` bytes could still
+    /// fit in the file. Reserving this instead of `count` keeps a corrupt
+    /// count from allocating gigabytes before the read fails.
+    pub fn capacity(&self, count: usize, min_size: usize) -> usize {
+        count.min((self.bytes.len() - self.position) / min_size.max(1))
+    }
+
+    pub fn skip(&mut self, count: usize) -> Result<(), String> {
+        self.take(count).map(|_| ())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    pub fn string(&mut self) -> Result<String, String> {
+        match self.u8()? {
+            0x00 => Ok(String::new()),
+            0x0b => {
+                let mut length = 0usize;
+                let mut shift = 0;
+                loop {
+                    let byte = self.u8()?;
+                    length |= usize::from(byte & 0x7f) << shift;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                    shift += 7;
+                    if shift > 35 {
+                        return Err("Invalid string length in database".into());
+                    }
+                }
+                Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+            }
+            other => Err(format!("Invalid string marker 0x{:02x} in database", other)),
+        }
+    }
+}
+
+/// Counterpart of [`DbReader`] for writing database files osu! stable can load.
+#[cfg(any(feature = "gui", test))]
+#[derive(Default)]
+pub struct DbWriter {
+    bytes: Vec<u8>,
+}
+
+#[cfg(any(feature = "gui", test))]
+impl DbWriter {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn string(&mut self, value: &str) {
+        if value.is_empty() {
+            self.bytes.push(0x00);
+            return;
+        }
+        self.bytes.push(0x0b);
+        let mut length = value.len();
+        loop {
+            let byte = (length & 0x7f) as u8;
+            length >>= 7;
+            if length == 0 {
+                self.bytes.push(byte);
+                break;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+}
+
+pub fn ticks_to_unix_ms(ticks: i64) -> Option<i64> {
+    if ticks <= TICKS_AT_UNIX_EPOCH {
+        return None;
+    }
+    Some((ticks - TICKS_AT_UNIX_EPOCH) / 10_000)
+}
+
+pub fn read_osu_db(path: &Path) -> Result<OsuDatabase, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_osu_db(&bytes)
+}
+
+fn parse_osu_db(bytes: &[u8]) -> Result<OsuDatabase, String> {
+    let mut reader = DbReader::new(bytes);
+
+    let version = reader.i32()?;
+    let _folder_count = reader.i32()?;
+    let _account_unlocked = reader.bool()?;
+    let _unlock_date = reader.i64()?;
+    let player_name = reader.string()?;
+    let count = reader.i32()?.max(0) as usize;
+
+    let mut beatmaps = Vec::with_capacity(reader.capacity(count, MIN_BEATMAP_ENTRY_SIZE));
+    for _ in 0..count {
+        beatmaps.push(read_beatmap(&mut reader, version)?);
+    }
+
+    Ok(OsuDatabase {
+        version,
+        player_name,
+        beatmaps,
+    })
+}
+
+fn read_beatmap(reader: &mut DbReader, version: i32) -> Result<OsuDbBeatmap, String> {
+    if version < VERSION_NO_ENTRY_SIZE {
+        reader.i32()?;
+    }
+    let artist = reader.string()?;
+    let artist_unicode = reader.string()?;
+    let title = reader.string()?;
+    let title_unicode = reader.string()?;
+    let creator = reader.string()?;
+    let difficulty = reader.string()?;
+    let audio_filename = reader.string()?;
+    let md5 = reader.string()?;
+    let file_name = reader.string()?;
+    let ranked_status = reader.u8()?;
+    let hit_circles = reader.u16()?;
+    let sliders = reader.u16()?;
+    let spinners = reader.u16()?;
+    let _last_modified = reader.i64()?;
+
+    let mut difficulty_values = [0f32; 4];
+    for value in difficulty_values.iter_mut() {
+        *value = if version < VERSION_FLOAT_DIFFICULTY {
+            f32::from(reader.u8()?)
+        } else {
+            reader.f32()?
+        };
+    }
+    let [_approach_rate, circle_size, hp_drain_rate, overall_difficulty] = difficulty_values;
+    let _slider_velocity = reader.f64()?;
+
+    let mut star_ratings = [None; 4];
+    if version >= VERSION_FLOAT_DIFFICULTY {
+        for rating in star_ratings.iter_mut() {
+            let pairs = reader.i32()?.max(0);
+            for _ in 0..pairs {
+                reader.u8()?;
+                let mods = reader.i32()?;
+                reader.u8()?;
+                let stars = if version >= VERSION_FLOAT_STAR_RATING {
+                    f64::from(reader.f32()?)
+                } else {
+                    reader.f64()?
+                };
+                if mods == 0 {
+                    *rating = Some(stars);
+                }
+            }
+        }
+    }
+
+    let drain_time_s = reader.i32()?;
+    let total_time_ms = reader.i32()?;
+    let _preview_time = reader.i32()?;
+    let point_count = reader.i32()?.max(0) as usize;
+    let mut timing_points = Vec::with_capacity(reader.capacity(point_count, TIMING_POINT_SIZE));
+    for _ in 0..point_count {
+        let beat_length = reader.f64()?;
+        let offset = reader.f64()?;
+        let uninherited = reader.bool()?;
+        timing_points.push((offset, beat_length, uninherited));
+    }
+
+    let _beatmap_id = reader.i32()?;
+    let _beatmap_set_id = reader.i32()?;
+    let _thread_id = reader.i32()?;
+    // Grades achieved in standard, taiko, catch and mania.
+    reader.skip(4)?;
+    let _local_offset = reader.u16()?;
+    let _stack_leniency = reader.f32()?;
+    let mode = reader.u8()?;
+    let source = reader.string()?;
+    let tags = reader.string()?;
+    let _online_offset = reader.u16()?;
+    let _title_font = reader.string()?;
+    let _unplayed = reader.bool()?;
+    let last_played = ticks_to_unix_ms(reader.i64()?);
+    let _is_osz2 = reader.bool()?;
+    let folder_name = reader.string()?;
+    let _last_checked = reader.i64()?;
+    // Ignore sound, ignore skin, disable storyboard, disable video, visual override.
+    reader.skip(5)?;
+    if version < VERSION_FLOAT_DIFFICULTY {
+        reader.u16()?;
+    }
+    let _last_modified_short = reader.i32()?;
+    let _scroll_speed = reader.u8()?;
+
+    Ok(OsuDbBeatmap {
+        artist,
+        artist_unicode,
+        title,
+        title_unicode,
+        creator,
+        version: difficulty,
+        audio_filename,
+        md5,
+        file_name,
+        ranked_status,
+        hit_circles,
+        sliders,
+        spinners,
+        circle_size,
+        hp_drain_rate,
+        overall_difficulty,
+        star_ratings,
+        drain_time_s,
+        total_time_ms,
+        timing_points,
+        mode,
+        source,
+        tags,
+        last_played,
+        folder_name,
+    })
+}
+
+pub fn ranked_status_name(status: u8) -> &'static str {
+    match status {
+        1 => "unsubmitted",
+        2 => "pending",
+        4 => "ranked",
+        5 => "approved",
+        6 => "qualified",
+        7 => "loved",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(writer: &mut DbWriter, bytes: &[u8]) {
+        writer.bytes.extend_from_slice(bytes);
+    }
+
+    /// A current-format osu!.db header announcing `count` beatmaps.
+    fn header(count: i32) -> DbWriter {
+        let mut writer = DbWriter::default();
+        writer.i32(VERSION_FLOAT_STAR_RATING);
+        writer.i32(1);
+        push(&mut writer, &[1]);
+        push(&mut writer, &0i64.to_le_bytes());
+        writer.string("player");
+        writer.i32(count);
+        writer
+    }
+
+    fn beatmap(writer: &mut DbWriter) {
+        for value in ["Artist", "", "Title", "", "Mapper", "Insane", "audio.mp3", "abc123", "map.osu"] {
+            writer.string(value);
+        }
+        push(writer, &[4]);
+        push(writer, &[10, 0, 20, 0, 1, 0]);
+        push(writer, &0i64.to_le_bytes());
+        for value in [9.0f32, 4.0, 6.0, 8.0] {
+            push(writer, &value.to_le_bytes());
+        }
+        push(writer, &1.4f64.to_le_bytes());
+        // Standard has a no-mod and a DT rating, the other modes none.
+        writer.i32(2);
+        for (mods, stars) in [(0, 5.5f32), (64, 7.0)] {
+            push(writer, &[0x08]);
+            writer.i32(mods);
+            push(writer, &[0x0c]);
+            push(writer, &stars.to_le_bytes());
+        }
+        for _ in 0..3 {
+            writer.i32(0);
+        }
+        writer.i32(90);
+        writer.i32(95_000);
+        writer.i32(1_000);
+        writer.i32(1);
+        push(writer, &500f64.to_le_bytes());
+        push(writer, &250f64.to_le_bytes());
+        push(writer, &[1]);
+        for id in [1, 2, 0] {
+            writer.i32(id);
+        }
+        push(writer, &[9, 9, 9, 9, 0, 0]);
+        push(writer, &0.7f32.to_le_bytes());
+        push(writer, &[0]);
+        writer.string("Game");
+        writer.string("tag");
+        push(writer, &[0, 0]);
+        writer.string("");
+        push(writer, &[0]);
+        push(writer, &(TICKS_AT_UNIX_EPOCH + 10_000_000).to_le_bytes());
+        push(writer, &[0]);
+        writer.string("1 Artist - Title");
+        push(writer, &0i64.to_le_bytes());
+        push(writer, &[0; 5]);
+        writer.i32(0);
+        push(writer, &[0]);
+    }
+
+    #[test]
+    fn reads_a_current_format_beatmap_entry() {
+        let mut writer = header(1);
+        beatmap(&mut writer);
+        let database = parse_osu_db(&writer.into_bytes()).unwrap();
+
+        assert_eq!(database.player_name, "player");
+        let [beatmap] = database.beatmaps.as_slice() else {
+            panic!("expected one beatmap");
+        };
+        assert_eq!(beatmap.artist, "Artist");
+        assert_eq!(beatmap.version, "Insane");
+        assert_eq!(beatmap.folder_name, "1 Artist - Title");
+        assert_eq!(beatmap.ranked_status, 4);
+        assert_eq!(beatmap.overall_difficulty, 8.0);
+        assert_eq!(beatmap.star_ratings, [Some(5.5), None, None, None]);
+        assert_eq!(beatmap.timing_points, vec![(250.0, 500.0, true)]);
+        assert_eq!(beatmap.source, "Game");
+        assert_eq!(beatmap.last_played, Some(1_000));
+    }
+
+    #[test]
+    fn huge_counts_fail_instead_of_allocating() {
+        let error = parse_osu_db(&header(i32::MAX).into_bytes()).err().unwrap();
+        assert!(error.starts_with("Unexpected end of database"), "{}", error);
+
+        let mut writer = header(1);
+        beatmap(&mut writer);
+        let mut bytes = writer.into_bytes();
+        bytes.truncate(bytes.len() - 10);
+        assert!(parse_osu_db(&bytes).is_err());
+    }
+
+    #[test]
+    fn strings_round_trip_through_writer_and_reader() {
+        let long = "x".repeat(300);
+        let mut writer = DbWriter::default();
+        for value in ["", "short", long.as_str()] {
+            writer.string(value);
+        }
+        let bytes = writer.into_bytes();
+        let mut reader = DbReader::new(&bytes);
+        assert_eq!(reader.string().unwrap(), "");
+        assert_eq!(reader.string().unwrap(), "short");
+        assert_eq!(reader.string().unwrap(), long);
+        assert_eq!(reader.capacity(10, 1), 0);
+    }
+}
@@ -0,0 +1,918 @@
+use std::fmt;
+use std::str::FromStr;
+
+const SECTION_ORDER: [&str; 8] = [
+    "General",
+    "Editor",
+    "Metadata",
+    "Difficulty",
+    "Events",
+    "TimingPoints",
+    "Colours",
+    "HitObjects",
+];
+
+pub const SLIDER: u32 = 1 << 1;
+pub const SPINNER: u32 = 1 << 3;
+pub const HOLD_NOTE: u32 = 1 << 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    None,
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::None => "",
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// A parsed .osu document. Every line keeps its original text, so writing an
+/// unmodified document reproduces the input byte for byte.
+#[derive(Clone, Debug)]
+pub struct OsuFile {
+    bom: bool,
+    newline: LineEnding,
+    preamble: Vec<Line>,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub name: String,
+    header: String,
+    header_ending: LineEnding,
+    pub lines: Vec<Line>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Line {
+    pub entry: Entry,
+    ending: LineEnding,
+}
+
+#[derive(Clone, Debug)]
+pub enum Entry {
+    Blank(String),
+    Comment(String),
+    Pair(Pair),
+    Event(Tracked<Event>),
+    TimingPoint(Tracked<TimingPoint>),
+    HitObject(Tracked<HitObject>),
+    Raw(String),
+}
+
+/// A typed value together with the line it was parsed from. The original text
+/// is written back for as long as the value still matches it.
+#[derive(Clone, Debug)]
+pub struct Tracked<T> {
+    raw: String,
+    pub value: T,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pair {
+    pub key: String,
+    pub value: String,
+    prefix: String,
+    suffix: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Background {
+        filename: String,
+        x_offset: i32,
+        y_offset: i32,
+    },
+    Video {
+        start_time: i32,
+        filename: String,
+        x_offset: i32,
+        y_offset: i32,
+    },
+    Break {
+        start_time: i32,
+        end_time: i32,
+    },
+    /// A storyboard object (`Sprite`, `Animation`, `Sample`) or one of its
+    /// indented command lines.
+    Storyboard(String),
+    /// Any other event, e.g. a `3,…` background colour change, kept verbatim.
+    Other(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimingPoint {
+    pub time: f64,
+    pub beat_length: f64,
+    pub meter: i32,
+    pub sample_set: i32,
+    pub sample_index: i32,
+    pub volume: i32,
+    pub uninherited: bool,
+    pub effects: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HitObject {
+    pub x: i32,
+    pub y: i32,
+    pub time: i32,
+    pub object_type: u32,
+    pub hit_sound: u32,
+    pub params: Vec<String>,
+    pub end_time: Option<i32>,
+    pub hit_sample: Option<String>,
+}
+
+pub trait LineValue: Sized + PartialEq + fmt::Display {
+    fn parse_line(line: &str) -> Option<Self>;
+}
+
+impl<T: LineValue> Tracked<T> {
+    fn parse(line: &str) -> Option<Self> {
+        T::parse_line(line).map(|value| Tracked {
+            raw: line.to_string(),
+            value,
+        })
+    }
+
+    fn write(&self, out: &mut String) {
+        if T::parse_line(&self.raw).as_ref() == Some(&self.value) {
+            out.push_str(&self.raw);
+        } else {
+            out.push_str(&self.value.to_string());
+        }
+    }
+}
+
+impl Pair {
+    pub fn new(section: &str, key: &str, value: &str) -> Self {
+        let separator = if matches!(section, "General" | "Editor") { ": " } else { ":" };
+        Pair {
+            key: key.to_string(),
+            value: value.replace(['\r', '\n'], ""),
+            prefix: format!("{}{}", key, separator),
+            suffix: String::new(),
+        }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let (key_part, rest) = line.split_once(':')?;
+        let value = rest.trim();
+        let leading = rest.len() - rest.trim_start().len();
+        let suffix = &rest[rest.trim_end().len()..];
+        Some(Pair {
+            key: key_part.trim().to_string(),
+            value: value.to_string(),
+            prefix: format!("{}:{}", key_part, &rest[..leading]),
+            suffix: if value.is_empty() { String::new() } else { suffix.to_string() },
+        })
+    }
+}
+
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.prefix, self.value, self.suffix)
+    }
+}
+
+impl LineValue for Event {
+    fn parse_line(line: &str) -> Option<Self> {
+        if line.starts_with(' ') || line.starts_with('_') {
+            return Some(Event::Storyboard(line.to_string()));
+        }
+        let fields = split_fields(line);
+        let int_at = |index: usize| fields.get(index).and_then(|v| parse_int(v)).unwrap_or(0);
+        let event = match fields[0].trim() {
+            "0" | "Background" if fields.len() >= 3 => Event::Background {
+                filename: unquote(&fields[2]),
+                x_offset: int_at(3),
+                y_offset: int_at(4),
+            },
+            "1" | "Video" if fields.len() >= 3 => Event::Video {
+                start_time: int_at(1),
+                filename: unquote(&fields[2]),
+                x_offset: int_at(3),
+                y_offset: int_at(4),
+            },
+            "2" | "Break" if fields.len() >= 3 => Event::Break {
+                start_time: int_at(1),
+                end_time: int_at(2),
+            },
+            "4" | "Sprite" | "5" | "Sample" | "6" | "Animation" => Event::Storyboard(line.to_string()),
+            _ => Event::Other(line.to_string()),
+        };
+        Some(event)
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Background {
+                filename,
+                x_offset,
+                y_offset,
+            } => write!(f, "0,0,\"{}\",{},{}", filename, x_offset, y_offset),
+            Event::Video {
+                start_time,
+                filename,
+                x_offset,
+                y_offset,
+            } => write!(f, "Video,{},\"{}\",{},{}", start_time, filename, x_offset, y_offset),
+            Event::Break { start_time, end_time } => write!(f, "2,{},{}", start_time, end_time),
+            Event::Storyboard(raw) | Event::Other(raw) => f.write_str(raw),
+        }
+    }
+}
+
+impl LineValue for TimingPoint {
+    fn parse_line(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() < 2 {
+            return None;
+        }
+        let int_at = |index: usize, default: i32| {
+            fields.get(index).and_then(|v| parse_int(v)).unwrap_or(default)
+        };
+        Some(TimingPoint {
+            time: fields[0].parse().ok()?,
+            beat_length: fields[1].parse().ok()?,
+            meter: int_at(2, 4),
+            sample_set: int_at(3, 0),
+            sample_index: int_at(4, 0),
+            volume: int_at(5, 100),
+            uninherited: int_at(6, 1) != 0,
+            effects: int_at(7, 0),
+        })
+    }
+}
+
+impl fmt::Display for TimingPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            self.time,
+            self.beat_length,
+            self.meter,
+            self.sample_set,
+            self.sample_index,
+            self.volume,
+            u8::from(self.uninherited),
+            self.effects
+        )
+    }
+}
+
+impl LineValue for HitObject {
+    fn parse_line(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() < 5 {
+            return None;
+        }
+        let object_type: u32 = fields[3].parse().ok()?;
+        let rest = &fields[5..];
+        let field = |index: usize| rest.get(index).map(|s| s.to_string());
+        let (params, end_time, hit_sample) = if object_type & HOLD_NOTE != 0 {
+            let combined = rest.first()?;
+            match combined.split_once(':') {
+                Some((end, sample)) => (Vec::new(), Some(parse_int(end)?), Some(sample.to_string())),
+                None => (Vec::new(), Some(parse_int(combined)?), None),
+            }
+        } else if object_type & SPINNER != 0 {
+            (Vec::new(), Some(parse_int(rest.first()?)?), field(1))
+        } else if object_type & SLIDER != 0 {
+            (rest.iter().take(5).map(|s| s.to_string()).collect(), None, field(5))
+        } else {
+            (Vec::new(), None, field(0))
+        };
+        Some(HitObject {
+            x: parse_int(fields[0])?,
+            y: parse_int(fields[1])?,
+            time: parse_int(fields[2])?,
+            object_type,
+            hit_sound: fields[4].parse().ok()?,
+            params,
+            end_time,
+            hit_sample,
+        })
+    }
+}
+
+impl fmt::Display for HitObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{}",
+            self.x, self.y, self.time, self.object_type, self.hit_sound
+        )?;
+        for param in &self.params {
+            write!(f, ",{}", param)?;
+        }
+        if self.object_type & HOLD_NOTE != 0 {
+            write!(f, ",{}", self.end_time.unwrap_or(self.time))?;
+            if let Some(sample) = &self.hit_sample {
+                write!(f, ":{}", sample)?;
+            }
+            return Ok(());
+        }
+        if let Some(end_time) = self.end_time {
+            write!(f, ",{}", end_time)?;
+        }
+        if let Some(sample) = &self.hit_sample {
+            write!(f, ",{}", sample)?;
+        }
+        Ok(())
+    }
+}
+
+impl HitObject {
+    pub fn is_hold(&self) -> bool {
+        self.object_type & HOLD_NOTE != 0
+    }
+
+    pub fn end_time(&self) -> i32 {
+        self.end_time.unwrap_or(self.time)
+    }
+
+    /// Custom sample index from the hit sample, 0 when it follows the timing point.
+    pub fn sample_index(&self) -> i32 {
+        self.sample_field(2).and_then(parse_int).unwrap_or(0)
+    }
+
+    pub fn set_sample_index(&mut self, index: i32) {
+        self.set_sample_field(2, &index.to_string());
+    }
+
+    /// File played instead of the regular hitsounds, if the object names one.
+    pub fn sample_filename(&self) -> Option<&str> {
+        self.sample_field(4).filter(|name| !name.is_empty())
+    }
+
+    pub fn set_sample_filename(&mut self, filename: &str) {
+        self.set_sample_field(4, filename);
+    }
+
+    /// Field `position` of the `normalSet:additionSet:index:volume:filename` hit sample.
+    fn sample_field(&self, position: usize) -> Option<&str> {
+        self.hit_sample.as_deref()?.split(':').nth(position)
+    }
+
+    fn set_sample_field(&mut self, position: usize, value: &str) {
+        let mut fields: Vec<String> = self
+            .hit_sample
+            .as_deref()
+            .unwrap_or("0:0:0:0:")
+            .split(':')
+            .map(str::to_string)
+            .collect();
+        while fields.len() <= position {
+            fields.push(if fields.len() == 4 { String::new() } else { "0".to_string() });
+        }
+        fields[position] = value.to_string();
+        self.hit_sample = Some(fields.join(":"));
+    }
+
+    pub fn column(&self, key_count: u32) -> u32 {
+        let column = (self.x as f32 * key_count as f32 / 512.0).floor() as i64;
+        column.clamp(0, i64::from(key_count.max(1)) - 1) as u32
+    }
+
+    /// Moves a mania note to `column`, using the x osu! writes for the centre
+    /// of that column.
+    pub fn set_column(&mut self, column: u32, key_count: u32) {
+        self.x = ((column as f32 + 0.5) * 512.0 / key_count.max(1) as f32).floor() as i32;
+    }
+}
+
+impl OsuFile {
+    pub fn parse(content: &str) -> OsuFile {
+        let (bom, content) = match content.strip_prefix('\u{feff}') {
+            Some(rest) => (true, rest),
+            None => (false, content),
+        };
+        let newline = if content.contains("\r\n") {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        };
+
+        let mut preamble = Vec::new();
+        let mut sections: Vec<Section> = Vec::new();
+
+        for chunk in content.split_inclusive('\n') {
+            let (text, ending) = if let Some(text) = chunk.strip_suffix("\r\n") {
+                (text, LineEnding::CrLf)
+            } else if let Some(text) = chunk.strip_suffix('\n') {
+                (text, LineEnding::Lf)
+            } else {
+                (chunk, LineEnding::None)
+            };
+            let trimmed = text.trim();
+
+            if trimmed.starts_with('[') && trimmed.ends_with(']') && trimmed.len() > 1 {
+                sections.push(Section {
+                    name: trimmed[1..trimmed.len() - 1].to_string(),
+                    header: text.to_string(),
+                    header_ending: ending,
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+
+            match sections.last_mut() {
+                Some(section) => {
+                    let entry = parse_entry(&section.name, text);
+                    section.lines.push(Line { entry, ending });
+                }
+                None => {
+                    let entry = if trimmed.is_empty() {
+                        Entry::Blank(text.to_string())
+                    } else {
+                        Entry::Raw(text.to_string())
+                    };
+                    preamble.push(Line { entry, ending });
+                }
+            }
+        }
+
+        OsuFile {
+            bom,
+            newline,
+            preamble,
+            sections,
+        }
+    }
+
+    /// The `N` of the `osu file format vN` header, if the file has one.
+    pub fn format_version(&self) -> Option<u32> {
+        self.preamble.iter().find_map(|line| match &line.entry {
+            Entry::Raw(text) => text.trim().strip_prefix("osu file format v")?.trim().parse().ok(),
+            _ => None,
+        })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    pub fn section_mut(&mut self, name: &str) -> Option<&mut Section> {
+        self.sections.iter_mut().find(|s| s.name == name)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.section(section)?.pairs().find(|p| p.key == key).map(|p| p.value.as_str())
+    }
+
+    pub fn get_parsed<T: FromStr>(&self, section: &str, key: &str) -> Option<T> {
+        self.get(section, key)?.parse().ok()
+    }
+
+    /// Sets `key` inside `section`, adding the key (and the section) in the
+    /// right place when the file does not have it yet. Line breaks are
+    /// dropped from the value so it cannot spill into the following lines.
+    pub fn set(&mut self, section: &str, key: &str, value: impl ToString) {
+        let value = value.to_string().replace(['\r', '\n'], "");
+        let newline = self.newline;
+        let index = match self.sections.iter().position(|s| s.name == section) {
+            Some(index) => index,
+            None => self.insert_section(section),
+        };
+        let target = &mut self.sections[index];
+
+        for line in target.lines.iter_mut() {
+            if let Entry::Pair(pair) = &mut line.entry {
+                if pair.key == key {
+                    pair.value = value;
+                    return;
+                }
+            }
+        }
+
+        let position = target
+            .lines
+            .iter()
+            .rposition(|line| !matches!(line.entry, Entry::Blank(_)))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        target.insert_line(position, Entry::Pair(Pair::new(section, key, &value)), newline);
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.section("Events")
+            .into_iter()
+            .flat_map(|s| s.lines.iter())
+            .filter_map(|line| match &line.entry {
+                Entry::Event(event) => Some(&event.value),
+                _ => None,
+            })
+    }
+
+    pub fn events_mut(&mut self) -> impl Iterator<Item = &mut Event> {
+        self.section_mut("Events")
+            .into_iter()
+            .flat_map(|s| s.lines.iter_mut())
+            .filter_map(|line| match &mut line.entry {
+                Entry::Event(event) => Some(&mut event.value),
+                _ => None,
+            })
+    }
+
+    pub fn timing_points(&self) -> impl Iterator<Item = &TimingPoint> {
+        self.section("TimingPoints")
+            .into_iter()
+            .flat_map(|s| s.lines.iter())
+            .filter_map(|line| match &line.entry {
+                Entry::TimingPoint(point) => Some(&point.value),
+                _ => None,
+            })
+    }
+
+    pub fn timing_points_mut(&mut self) -> impl Iterator<Item = &mut TimingPoint> {
+        self.section_mut("TimingPoints")
+            .into_iter()
+            .flat_map(|s| s.lines.iter_mut())
+            .filter_map(|line| match &mut line.entry {
+                Entry::TimingPoint(point) => Some(&mut point.value),
+                _ => None,
+            })
+    }
+
+    pub fn hit_objects(&self) -> impl Iterator<Item = &HitObject> {
+        self.section("HitObjects")
+            .into_iter()
+            .flat_map(|s| s.lines.iter())
+            .filter_map(|line| match &line.entry {
+                Entry::HitObject(object) => Some(&object.value),
+                _ => None,
+            })
+    }
+
+    pub fn hit_objects_mut(&mut self) -> impl Iterator<Item = &mut HitObject> {
+        self.section_mut("HitObjects")
+            .into_iter()
+            .flat_map(|s| s.lines.iter_mut())
+            .filter_map(|line| match &mut line.entry {
+                Entry::HitObject(object) => Some(&mut object.value),
+                _ => None,
+            })
+    }
+
+    pub fn background(&self) -> Option<&str> {
+        self.events().find_map(|event| match event {
+            Event::Background { filename, .. } => Some(filename.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn set_background(&mut self, new_filename: &str) -> bool {
+        for event in self.events_mut() {
+            if let Event::Background { filename, .. } = event {
+                *filename = new_filename.to_string();
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn video(&self) -> Option<&str> {
+        self.events().find_map(|event| match event {
+            Event::Video { filename, .. } => Some(filename.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Points the first Video event at `new_filename`. osu! only plays one
+    /// video, so any further Video events are removed.
+    pub fn set_video(&mut self, new_filename: &str) -> bool {
+        let mut found = false;
+        self.retain_events(|event| match event {
+            Event::Video { filename, .. } if !found => {
+                *filename = new_filename.to_string();
+                found = true;
+                true
+            }
+            Event::Video { .. } => false,
+            _ => true,
+        });
+        found
+    }
+
+    pub fn strip_videos(&mut self) {
+        self.retain_events(|event| !matches!(event, Event::Video { .. }));
+    }
+
+    /// Raw storyboard lines (objects and their indented commands) in [Events].
+    pub fn storyboard_lines(&self) -> impl Iterator<Item = &str> {
+        self.events().filter_map(|event| match event {
+            Event::Storyboard(raw) => Some(raw.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Removes every storyboard line from [Events], keeping backgrounds,
+    /// videos, breaks, colour changes and comments.
+    pub fn strip_storyboard(&mut self) {
+        self.retain_events(|event| !matches!(event, Event::Storyboard(_)));
+    }
+
+    /// Keeps the [Events] lines whose event `keep` returns true for; `keep`
+    /// may also edit the events it keeps.
+    fn retain_events(&mut self, mut keep: impl FnMut(&mut Event) -> bool) {
+        if let Some(section) = self.section_mut("Events") {
+            section.lines.retain_mut(|line| match &mut line.entry {
+                Entry::Event(event) => keep(&mut event.value),
+                _ => true,
+            });
+        }
+    }
+
+    /// Appends storyboard lines after the last entry in [Events].
+    pub fn append_storyboard(&mut self, lines: impl IntoIterator<Item = String>) {
+        let newline = self.newline;
+        let index = match self.sections.iter().position(|s| s.name == "Events") {
+            Some(index) => index,
+            None => self.insert_section("Events"),
+        };
+        let section = &mut self.sections[index];
+        let start = section
+            .lines
+            .iter()
+            .rposition(|line| !matches!(line.entry, Entry::Blank(_)))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        for (position, line) in (start..).zip(lines) {
+            let entry = Entry::Event(Tracked {
+                value: Event::Storyboard(line.clone()),
+                raw: line,
+            });
+            section.insert_line(position, entry, newline);
+        }
+    }
+
+    fn insert_section(&mut self, name: &str) -> usize {
+        let rank = |n: &str| SECTION_ORDER.iter().position(|s| *s == n).unwrap_or(SECTION_ORDER.len());
+        let position = self
+            .sections
+            .iter()
+            .position(|s| rank(&s.name) > rank(name))
+            .unwrap_or(self.sections.len());
+
+        let newline = self.newline;
+        let previous_lines = if position == 0 {
+            &mut self.preamble
+        } else {
+            &mut self.sections[position - 1].lines
+        };
+        if let Some(last) = previous_lines.last_mut() {
+            last.ending = newline;
+        }
+        if !matches!(previous_lines.last().map(|l| &l.entry), Some(Entry::Blank(_)) | None) {
+            previous_lines.push(Line {
+                entry: Entry::Blank(String::new()),
+                ending: newline,
+            });
+        }
+
+        let at_end = position == self.sections.len();
+        let mut lines = Vec::new();
+        if !at_end {
+            lines.push(Line {
+                entry: Entry::Blank(String::new()),
+                ending: newline,
+            });
+        }
+        self.sections.insert(
+            position,
+            Section {
+                name: name.to_string(),
+                header: format!("[{}]", name),
+                header_ending: newline,
+                lines,
+            },
+        );
+        position
+    }
+}
+
+impl Section {
+    pub fn pairs(&self) -> impl Iterator<Item = &Pair> {
+        self.lines.iter().filter_map(|line| match &line.entry {
+            Entry::Pair(pair) => Some(pair),
+            _ => None,
+        })
+    }
+
+    pub fn insert_line(&mut self, position: usize, entry: Entry, newline: LineEnding) {
+        let mut ending = newline;
+        if position == self.lines.len() {
+            if let Some(last) = self.lines.last_mut() {
+                ending = last.ending;
+                last.ending = newline;
+            }
+        }
+        self.lines.insert(position, Line { entry, ending });
+    }
+}
+
+impl fmt::Display for OsuFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        if self.bom {
+            out.push('\u{feff}');
+        }
+        for line in &self.preamble {
+            line.write(&mut out);
+        }
+        for section in &self.sections {
+            out.push_str(&section.header);
+            out.push_str(section.header_ending.as_str());
+            for line in &section.lines {
+                line.write(&mut out);
+            }
+        }
+        f.write_str(&out)
+    }
+}
+
+impl Line {
+    fn write(&self, out: &mut String) {
+        match &self.entry {
+            Entry::Blank(text) | Entry::Comment(text) | Entry::Raw(text) => out.push_str(text),
+            Entry::Pair(pair) => out.push_str(&pair.to_string()),
+            Entry::Event(event) => event.write(out),
+            Entry::TimingPoint(point) => point.write(out),
+            Entry::HitObject(object) => object.write(out),
+        }
+        out.push_str(self.ending.as_str());
+    }
+}
+
+fn parse_entry(section: &str, text: &str) -> Entry {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Entry::Blank(text.to_string());
+    }
+    if trimmed.starts_with("//") {
+        return Entry::Comment(text.to_string());
+    }
+    let parsed = match section {
+        "General" | "Editor" | "Metadata" | "Difficulty" | "Colours" => Pair::parse(text).map(Entry::Pair),
+        "Events" => Tracked::parse(text).map(Entry::Event),
+        "TimingPoints" => Tracked::parse(text).map(Entry::TimingPoint),
+        "HitObjects" => Tracked::parse(text).map(Entry::HitObject),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Entry::Raw(text.to_string()))
+}
+
+fn parse_int(value: &str) -> Option<i32> {
+    let value = value.trim();
+    value
+        .parse::<i32>()
+        .ok()
+        .or_else(|| value.parse::<f64>().ok().map(|v| v as i32))
+}
+
+pub(crate) fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches('"').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = concat!(
+        "osu file format v14\r\n",
+        "\r\n",
+        "[General]\r\n",
+        "AudioFilename: audio.mp3\r\n",
+        "Mode: 3\r\n",
+        "\r\n",
+        "[Metadata]\r\n",
+        "Title:Song  \r\n",
+        "Artist:Someone\r\n",
+        "Version:Hard\r\n",
+        "// a comment\r\n",
+        "\r\n",
+        "[Difficulty]\r\n",
+        "CircleSize:4\r\n",
+        "OverallDifficulty:8.5\r\n",
+        "\r\n",
+        "[Events]\r\n",
+        "//Background and Video events\r\n",
+        "0,0,\"bg.jpg\",0,0\r\n",
+        "Video,-200,\"clip.avi\"\r\n",
+        "2,1000,2000\r\n",
+        "3,100,163,162,255\r\n",
+        "Sprite,Foreground,Centre,\"sb\\star.png\",320,240\r\n",
+        " F,0,0,500,1,0\r\n",
+        "\r\n",
+        "[TimingPoints]\r\n",
+        "0,500,4,2,1,60,1,0\r\n",
+        "1000,-50,4,2,1,60,0,0\r\n",
+        "\r\n",
+        "[HitObjects]\r\n",
+        "64,192,0,1,0,0:0:0:0:\r\n",
+        "192,192,500,128,0,900:0:0:0:0:\r\n",
+        "320, 192, 1000, 1, 2\r\n",
+        "448,192,1500,1,0,0:0:0:0:"
+    );
+
+    #[test]
+    fn unmodified_document_round_trips_byte_for_byte() {
+        for content in [
+            SAMPLE.to_string(),
+            SAMPLE.replace("\r\n", "\n"),
+            format!("\u{feff}{}\n", SAMPLE),
+            "[General]\nOdd line without colon\n\n\n[Unknown]\nwhatever,1,2".to_string(),
+        ] {
+            assert_eq!(OsuFile::parse(&content).to_string(), content);
+        }
+    }
+
+    #[test]
+    fn edits_only_rewrite_the_changed_lines() {
+        let mut osu = OsuFile::parse(SAMPLE);
+        osu.set("Metadata", "Version", "Insane");
+        osu.set("Metadata", "Source", "Game");
+        osu.set("Editor", "BeatDivisor", 4);
+        osu.hit_objects_mut().nth(1).unwrap().time = 600;
+
+        let written = osu.to_string();
+        let expected = SAMPLE
+            .replace("// a comment\r\n", "// a comment\r\nSource:Game\r\n")
+            .replace("Version:Hard", "Version:Insane")
+            .replace("192,192,500,128", "192,192,600,128")
+            .replace("\r\n[Metadata]", "\r\n[Editor]\r\nBeatDivisor: 4\r\n\r\n[Metadata]");
+        assert_eq!(written, expected);
+        assert_eq!(OsuFile::parse(&written).get("Metadata", "Version"), Some("Insane"));
+    }
+
+    #[test]
+    fn set_values_stay_on_one_line() {
+        let mut osu = OsuFile::parse(SAMPLE);
+        osu.set("Metadata", "Version", "Hard\r\n[Events]");
+        osu.set("Metadata", "Creator", "a\nb");
+
+        let reparsed = OsuFile::parse(&osu.to_string());
+        assert_eq!(reparsed.get("Metadata", "Version"), Some("Hard[Events]"));
+        assert_eq!(reparsed.get("Metadata", "Creator"), Some("ab"));
+        assert_eq!(reparsed.sections.iter().filter(|s| s.name == "Events").count(), 1);
+    }
+
+    #[test]
+    fn typed_accessors_read_events_timing_points_and_objects() {
+        let osu = OsuFile::parse(SAMPLE);
+        assert_eq!(osu.format_version(), Some(14));
+        assert_eq!(osu.get_parsed::<f32>("Difficulty", "OverallDifficulty"), Some(8.5));
+        assert_eq!(osu.get("Metadata", "Title"), Some("Song"));
+        assert_eq!(osu.background(), Some("bg.jpg"));
+        assert_eq!(osu.video(), Some("clip.avi"));
+        assert_eq!(osu.storyboard_lines().count(), 2);
+
+        let points: Vec<&TimingPoint> = osu.timing_points().collect();
+        assert!(points[0].uninherited && !points[1].uninherited);
+        let objects: Vec<&HitObject> = osu.hit_objects().collect();
+        assert_eq!(objects.len(), 4);
+        assert!(objects[1].is_hold());
+        assert_eq!(objects[1].end_time(), 900);
+        assert_eq!(objects[2].column(4), 2);
+    }
+
+    #[test]
+    fn stripping_the_storyboard_keeps_other_events() {
+        let mut osu = OsuFile::parse(SAMPLE);
+        osu.strip_storyboard();
+        let written = osu.to_string();
+        assert!(written.contains("2,1000,2000\r\n3,100,163,162,255\r\n\r\n[TimingPoints]"), "{}", written);
+        assert!(!written.contains("Sprite") && !written.contains(" F,"));
+    }
+}
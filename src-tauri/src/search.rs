@@ -0,0 +1,426 @@
+use crate::commands::Beatmap;
+
+const TITLE_WEIGHT: u32 = 6;
+const ARTIST_WEIGHT: u32 = 5;
+const VERSION_WEIGHT: u32 = 4;
+const CREATOR_WEIGHT: u32 = 4;
+const SOURCE_WEIGHT: u32 = 2;
+const TAGS_WEIGHT: u32 = 1;
+
+/// Weighted match of every free-text term against the indexed metadata;
+/// `None` when some term matches nothing.
+fn score(beatmap: &Beatmap, terms: &[String]) -> Option<u32> {
+    let metadata = &beatmap.metadata;
+    let fields = [
+        (fold(&metadata.title), TITLE_WEIGHT),
+        (fold(&metadata.title_unicode), TITLE_WEIGHT),
+        (fold(&metadata.artist), ARTIST_WEIGHT),
+        (fold(&metadata.artist_unicode), ARTIST_WEIGHT),
+        (fold(&metadata.version), VERSION_WEIGHT),
+        (fold(&metadata.creator), CREATOR_WEIGHT),
+        (fold(&metadata.source), SOURCE_WEIGHT),
+        (fold(&metadata.tags), TAGS_WEIGHT),
+        (fold(&beatmap.id), TAGS_WEIGHT),
+    ];
+
+    let mut total = 0;
+    for term in terms {
+        let best = fields
+            .iter()
+            .map(|(field, weight)| field_score(field, term) * weight)
+            .max()
+            .unwrap_or(0);
+        if best == 0 {
+            return None;
+        }
+        total += best;
+    }
+    Some(total)
+}
+
+fn field_score(field: &str, term: &str) -> u32 {
+    if field.is_empty() || !field.contains(term) {
+        return 0;
+    }
+    if field == term {
+        return 4;
+    }
+    let word_match = field
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word == term);
+    if word_match {
+        3
+    } else if field.starts_with(term) || field.split_whitespace().any(|word| word.starts_with(term)) {
+        2
+    } else {
+        1
+    }
+}
+
+fn fold(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    Exact,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+enum Filter {
+    Number {
+        key: NumberKey,
+        operator: Operator,
+        value: f64,
+    },
+    Text {
+        key: TextKey,
+        operator: Operator,
+        value: String,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum NumberKey {
+    Keys,
+    OverallDifficulty,
+    HpDrainRate,
+    Bpm,
+    Length,
+    Stars,
+    Mode,
+}
+
+#[derive(Clone, Copy)]
+enum TextKey {
+    Creator,
+    Artist,
+    Title,
+    Version,
+    Source,
+    Tags,
+    Status,
+}
+
+/// Runs an osu!-style filter query such as `keys=7 od>=8 bpm<200 creator=name`.
+/// Terms that are not `key<op>value` comparisons are matched as free text.
+pub fn query(beatmaps: &[Beatmap], query: &str) -> Result<Vec<Beatmap>, String> {
+    let mut filters = Vec::new();
+    let mut terms = Vec::new();
+    for token in tokenize(query) {
+        match parse_filter(&token)? {
+            Some(filter) => filters.push(filter),
+            None => terms.push(fold(token.trim_matches('"'))),
+        }
+    }
+    terms.retain(|term| !term.is_empty());
+
+    let mut scored: Vec<(u32, &Beatmap)> = beatmaps
+        .iter()
+        .filter(|beatmap| filters.iter().all(|filter| matches_filter(beatmap, filter)))
+        .filter_map(|beatmap| {
+            if terms.is_empty() {
+                Some((0, beatmap))
+            } else {
+                score(beatmap, &terms).map(|score| (score, beatmap))
+            }
+        })
+        .collect();
+    if !terms.is_empty() {
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.id.cmp(&b.1.id)));
+    }
+    Ok(scored.into_iter().map(|(_, beatmap)| beatmap.clone()).collect())
+}
+
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        if c == '"' {
+            quoted = !quoted;
+            current.push(c);
+        } else if c.is_whitespace() && !quoted {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_filter(token: &str) -> Result<Option<Filter>, String> {
+    let key_len = token
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(token.len());
+    if key_len == 0 {
+        return Ok(None);
+    }
+    let (key, rest) = token.split_at(key_len);
+    let (operator, value) = match parse_operator(rest) {
+        Some(parsed) => parsed,
+        None => return Ok(None),
+    };
+    let value = value.trim_matches('"');
+    let key = key.to_lowercase();
+
+    let number_key = match key.as_str() {
+        "keys" | "key" | "k" => Some(NumberKey::Keys),
+        "od" | "accuracy" => Some(NumberKey::OverallDifficulty),
+        "hp" | "drain" => Some(NumberKey::HpDrainRate),
+        "bpm" => Some(NumberKey::Bpm),
+        "length" | "len" => Some(NumberKey::Length),
+        "stars" | "star" | "sr" => Some(NumberKey::Stars),
+        "mode" | "m" => Some(NumberKey::Mode),
+        _ => None,
+    };
+    if let Some(key) = number_key {
+        let parsed = match key {
+            NumberKey::Length => parse_length(value),
+            NumberKey::Mode => parse_mode(value).or_else(|| value.parse().ok()),
+            _ => value.parse().ok(),
+        };
+        let value = parsed.ok_or_else(|| format!("Invalid value in filter: {}", token))?;
+        return Ok(Some(Filter::Number { key, operator, value }));
+    }
+
+    let text_key = match key.as_str() {
+        "creator" | "mapper" | "author" => TextKey::Creator,
+        "artist" => TextKey::Artist,
+        "title" => TextKey::Title,
+        "version" | "diff" | "difficulty" => TextKey::Version,
+        "source" => TextKey::Source,
+        "tag" | "tags" => TextKey::Tags,
+        "status" => TextKey::Status,
+        _ => return Ok(None),
+    };
+    if !matches!(operator, Operator::Equal | Operator::Exact | Operator::NotEqual) {
+        return Err(format!("Unsupported comparison in filter: {}", token));
+    }
+    let value = match text_key {
+        TextKey::Status => parse_status(value)
+            .ok_or_else(|| format!("Unknown status in filter: {}", token))?
+            .to_string(),
+        _ => fold(value),
+    };
+    Ok(Some(Filter::Text {
+        key: text_key,
+        operator,
+        value,
+    }))
+}
+
+fn parse_operator(rest: &str) -> Option<(Operator, &str)> {
+    let operators = [
+        ("==", Operator::Exact),
+        ("!=", Operator::NotEqual),
+        (">=", Operator::GreaterOrEqual),
+        ("<=", Operator::LessOrEqual),
+        (">:", Operator::GreaterOrEqual),
+        ("<:", Operator::LessOrEqual),
+        ("=", Operator::Equal),
+        (":", Operator::Equal),
+        (">", Operator::Greater),
+        ("<", Operator::Less),
+    ];
+    operators
+        .iter()
+        .find_map(|(symbol, operator)| rest.strip_prefix(symbol).map(|value| (*operator, value)))
+        .filter(|(_, value)| !value.is_empty())
+}
+
+fn parse_length(value: &str) -> Option<f64> {
+    if let Some((minutes, seconds)) = value.split_once(':') {
+        return Some(minutes.parse::<f64>().ok()? * 60.0 + seconds.parse::<f64>().ok()?);
+    }
+    let units = [("ms", 0.001), ("s", 1.0), ("m", 60.0), ("h", 3600.0)];
+    for (suffix, scale) in units {
+        if let Some(number) = value.strip_suffix(suffix) {
+            return number.parse::<f64>().ok().map(|n| n * scale);
+        }
+    }
+    value.parse().ok()
+}
+
+fn parse_mode(value: &str) -> Option<f64> {
+    match value.to_lowercase().as_str() {
+        "osu" | "std" | "standard" => Some(0.0),
+        "taiko" => Some(1.0),
+        "catch" | "fruits" | "ctb" => Some(2.0),
+        "mania" => Some(3.0),
+        _ => None,
+    }
+}
+
+fn parse_status(value: &str) -> Option<&'static str> {
+    let status = match value.to_lowercase().as_str() {
+        "r" | "ranked" => "ranked",
+        "a" | "approved" => "approved",
+        "q" | "qualified" => "qualified",
+        "l" | "loved" => "loved",
+        // osu!.db stores graveyarded maps as pending.
+        "p" | "pending" | "wip" | "g" | "graveyard" => "pending",
+        "u" | "unsubmitted" => "unsubmitted",
+        "unknown" => "unknown",
+        _ => return None,
+    };
+    Some(status)
+}
+
+fn matches_filter(beatmap: &Beatmap, filter: &Filter) -> bool {
+    match filter {
+        Filter::Number { key, operator, value } => {
+            let metadata = &beatmap.metadata;
+            let (actual, tolerance) = match key {
+                NumberKey::Keys => (f64::from(metadata.key_count), 0.5),
+                NumberKey::OverallDifficulty => (f64::from(metadata.overall_difficulty), 0.05),
+                NumberKey::HpDrainRate => (f64::from(metadata.hp_drain_rate), 0.05),
+                NumberKey::Bpm => (beatmap.timing.dominant_bpm, 0.005),
+                NumberKey::Length => (f64::from(beatmap.timing.drain_time_ms) / 1000.0, 0.5),
+                NumberKey::Stars => match beatmap.star_rating {
+                    Some(stars) => (stars, 0.005),
+                    None => return false,
+                },
+                NumberKey::Mode => (f64::from(metadata.mode), 0.5),
+            };
+            match operator {
+                Operator::Equal | Operator::Exact => (actual - value).abs() < tolerance,
+                Operator::NotEqual => (actual - value).abs() >= tolerance,
+                Operator::Less => actual < *value,
+                Operator::LessOrEqual => actual <= *value,
+                Operator::Greater => actual > *value,
+                Operator::GreaterOrEqual => actual >= *value,
+            }
+        }
+        Filter::Text { key, operator, value } => {
+            let metadata = &beatmap.metadata;
+            let actual = match key {
+                TextKey::Creator => fold(&metadata.creator),
+                TextKey::Artist => fold(&metadata.artist),
+                TextKey::Title => fold(&metadata.title),
+                TextKey::Version => fold(&metadata.version),
+                TextKey::Source => fold(&metadata.source),
+                TextKey::Tags => fold(&metadata.tags),
+                TextKey::Status => beatmap.status.clone().unwrap_or_else(|| "unknown".to_string()),
+            };
+            match operator {
+                Operator::Exact => actual == *value,
+                Operator::NotEqual => !actual.contains(value.as_str()),
+                _ => actual.contains(value.as_str()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{BeatmapMetadata, BeatmapTiming};
+
+    fn beatmap(id: &str, keys: u32, od: f32, bpm: f64, creator: &str, status: Option<&str>) -> Beatmap {
+        Beatmap {
+            id: id.to_string(),
+            path: format!("Songs/{}.osu", id),
+            metadata: BeatmapMetadata {
+                title: format!("{} song", id),
+                creator: creator.to_string(),
+                mode: 3,
+                key_count: keys,
+                overall_difficulty: od,
+                ..BeatmapMetadata::default()
+            },
+            timing: BeatmapTiming {
+                dominant_bpm: bpm,
+                drain_time_ms: 90_000,
+                ..BeatmapTiming::default()
+            },
+            star_rating: Some(f64::from(od) / 2.0),
+            status: status.map(str::to_string),
+            modified: 0,
+            size: 0,
+            md5: None,
+            last_played: None,
+        }
+    }
+
+    fn library() -> Vec<Beatmap> {
+        vec![
+            beatmap("alpha", 4, 8.0, 180.0, "Mapper One", Some("ranked")),
+            beatmap("beta", 7, 8.5, 150.0, "Mapper Two", Some("loved")),
+            beatmap("gamma", 7, 7.0, 220.0, "Mapper One", None),
+            beatmap("delta", 4, 5.0, 120.0, "Mapper Three", Some("pending")),
+        ]
+    }
+
+    fn ids(query_text: &str) -> Vec<String> {
+        query(&library(), query_text)
+            .unwrap()
+            .into_iter()
+            .map(|beatmap| beatmap.id)
+            .collect()
+    }
+
+    #[test]
+    fn numeric_filters_combine() {
+        assert_eq!(ids("keys=7"), ["beta", "gamma"]);
+        assert_eq!(ids("keys=7 od>=8"), ["beta"]);
+        assert_eq!(ids("bpm<200"), ["alpha", "beta", "delta"]);
+        assert_eq!(ids("k!=7 sr>3.9"), ["alpha"]);
+        assert_eq!(ids("length=1:30 mode=mania"), ["alpha", "beta", "gamma", "delta"]);
+    }
+
+    #[test]
+    fn text_filters_and_free_terms() {
+        assert_eq!(ids("creator=\"mapper one\""), ["alpha", "gamma"]);
+        assert_eq!(ids("creator==\"mapper\""), Vec::<String>::new());
+        assert_eq!(ids("status=r"), ["alpha"]);
+        assert_eq!(ids("status=graveyard"), ["delta"]);
+        assert_eq!(ids("status=unknown"), ["gamma"]);
+        assert_eq!(ids("keys=7 gamma"), ["gamma"]);
+    }
+
+    #[test]
+    fn free_text_ranks_by_field_and_match_quality() {
+        let titled = |id: &str, title: &str, tags: &str| {
+            let mut beatmap = beatmap(id, 4, 8.0, 180.0, "Mapper", None);
+            beatmap.metadata.title = title.to_string();
+            beatmap.metadata.tags = tags.to_string();
+            beatmap
+        };
+        let library = vec![
+            titled("a", "Other", "star"),
+            titled("b", "Lodestar", ""),
+            titled("c", "Starlight", ""),
+            titled("d", "Star", ""),
+            titled("e", "Star Rail", ""),
+        ];
+
+        let ranked: Vec<String> = query(&library, "star")
+            .unwrap()
+            .into_iter()
+            .map(|beatmap| beatmap.id)
+            .collect();
+        // Exact, whole word, prefix, substring; a tag match comes after any title match.
+        assert_eq!(ranked, ["d", "e", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn malformed_filters_are_errors() {
+        for bad in ["od>=fast", "creator>abc", "status=banana"] {
+            assert!(query(&library(), bad).is_err(), "{} should not parse", bad);
+        }
+        // Unknown keys and bare operators fall back to free-text search.
+        assert_eq!(ids("foo=bar"), Vec::<String>::new());
+        assert_eq!(ids("=7"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::osu_file::{split_fields, Entry, OsuFile};
+
+/// Storyboard lines of one difficulty: the set's .osb followed by the
+/// difficulty's own [Events] storyboard, with variables already expanded.
+pub struct Storyboard {
+    lines: Vec<String>,
+}
+
+/// Reads the storyboard `osu` shows when played from `beatmap_dir`.
+pub fn load(osu: &OsuFile, beatmap_dir: &Path) -> Result<Storyboard, String> {
+    let mut lines = Vec::new();
+    if let Some(path) = set_storyboard_path(beatmap_dir) {
+        let bytes = fs::read(&path).map_err(|e| format!("Failed to read storyboard {}: {}", path.display(), e))?;
+        let osb = OsuFile::parse(&String::from_utf8_lossy(&bytes));
+        lines.extend(expanded_lines(&osb));
+    }
+    lines.extend(expanded_lines(osu));
+    Ok(Storyboard { lines })
+}
+
+impl Storyboard {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Every file the storyboard draws or plays, relative to the beatmap
+    /// folder with `/` separators. Animations list each of their frames.
+    pub fn files(&self) -> BTreeSet<String> {
+        let mut files = BTreeSet::new();
+        for line in &self.lines {
+            let fields = split_fields(line);
+            let Some(path) = object_path(&fields).filter(|path| !leaves_folder(path)) else {
+                continue;
+            };
+            match animation_frames(&fields) {
+                Some(frames) => files.extend((0..frames).map(|frame| frame_name(&path, frame))),
+                None => {
+                    files.insert(path);
+                }
+            }
+        }
+        files
+    }
+
+    /// Object paths that point outside the beatmap folder. `relocated` drops
+    /// these objects since their files can't be packed.
+    pub fn outside_files(&self) -> BTreeSet<String> {
+        self.lines
+            .iter()
+            .filter_map(|line| object_path(&split_fields(line)))
+            .filter(|path| leaves_folder(path))
+            .collect()
+    }
+
+    /// The storyboard lines with every file path moved under `folder`.
+    /// Objects whose file lies outside the beatmap folder are dropped along
+    /// with their commands.
+    pub fn relocated(&self, folder: &str) -> Vec<String> {
+        let mut lines = Vec::with_capacity(self.lines.len());
+        let mut dropping = false;
+        for line in &self.lines {
+            if is_command(line) {
+                if !dropping {
+                    lines.push(line.clone());
+                }
+                continue;
+            }
+            let mut fields = split_fields(line);
+            dropping = false;
+            match object_path(&fields) {
+                Some(path) if leaves_folder(&path) => dropping = true,
+                Some(path) => {
+                    fields[3] = format!("\"{}/{}\"", folder, path);
+                    lines.push(fields.join(","));
+                }
+                None => lines.push(line.clone()),
+            }
+        }
+        lines
+    }
+}
+
+fn is_command(line: &str) -> bool {
+    line.starts_with(' ') || line.starts_with('_')
+}
+
+fn leaves_folder(path: &str) -> bool {
+    path.split('/').any(|segment| segment == "..")
+}
+
+/// The file of a `Sprite`, `Animation` or `Sample` object line with `/`
+/// separators. Command lines (indented) and other events carry no file.
+fn object_path(fields: &[String]) -> Option<String> {
+    let kind = fields[0].as_str();
+    if is_command(kind) {
+        return None;
+    }
+    match kind.trim() {
+        "Sprite" | "4" | "Animation" | "6" | "Sample" | "5" => {}
+        _ => return None,
+    }
+    let path = fields.get(3)?.trim().trim_matches('"').replace('\\', "/");
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty() && *s != ".").collect();
+    if segments.is_empty() {
+        return None;
+    }
+    Some(segments.join("/"))
+}
+
+fn animation_frames(fields: &[String]) -> Option<u32> {
+    match fields[0].trim() {
+        "Animation" | "6" => fields.get(6)?.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// osu! loads animation frame `n` of `sb/fire.png` from `sb/fire{n}.png`.
+fn frame_name(path: &str, frame: u32) -> String {
+    let file_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match path[file_start..].rfind('.') {
+        Some(dot) => {
+            let dot = file_start + dot;
+            format!("{}{}{}", &path[..dot], frame, &path[dot..])
+        }
+        None => format!("{}{}", path, frame),
+    }
+}
+
+/// The set-wide .osb in `dir`. Sets normally have exactly one; if there are
+/// several the first by name is used.
+fn set_storyboard_path(dir: &Path) -> Option<PathBuf> {
+    let mut candidates: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("osb"))
+        })
+        .collect();
+    candidates.sort();
+    candidates.into_iter().next()
+}
+
+/// Storyboard lines of `file` with `$name=value` entries from its [Variables]
+/// section substituted, longest names first so `$a` never clobbers `$ab`.
+fn expanded_lines(file: &OsuFile) -> Vec<String> {
+    let mut variables: Vec<(String, String)> = file
+        .section("Variables")
+        .into_iter()
+        .flat_map(|section| section.lines.iter())
+        .filter_map(|line| match &line.entry {
+            Entry::Raw(text) => {
+                let (name, value) = text.trim().split_once('=')?;
+                name.starts_with('$').then(|| (name.to_string(), value.to_string()))
+            }
+            _ => None,
+        })
+        .collect();
+    variables.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+
+    file.storyboard_lines()
+        .map(|line| {
+            variables
+                .iter()
+                .fold(line.to_string(), |line, (name, value)| line.replace(name, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storyboard(lines: &[&str]) -> Storyboard {
+        Storyboard {
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn lists_files_and_animation_frames() {
+        let storyboard = storyboard(&[
+            "Sprite,Foreground,Centre,\"sb\\star.png\",320,240",
+            " F,0,0,500,1,0",
+            "Animation,Background,Centre,\"./sb/fire.png\",320,240,2,50,LoopForever",
+            "Sample,0,0,\"hit.wav\",100",
+        ]);
+        let files: Vec<_> = storyboard.files().into_iter().collect();
+        assert_eq!(files, ["hit.wav", "sb/fire0.png", "sb/fire1.png", "sb/star.png"]);
+    }
+
+    #[test]
+    fn relocation_drops_objects_outside_the_folder() {
+        let storyboard = storyboard(&[
+            "Sprite,Foreground,Centre,\"../other/star.png\",320,240",
+            " F,0,0,500,1,0",
+            "_M,0,0,500,320,240",
+            "Sprite,Foreground,Centre,\"sb/star.png\",320,240",
+            " F,0,0,500,1,0",
+        ]);
+        assert_eq!(storyboard.outside_files().into_iter().collect::<Vec<_>>(), ["../other/star.png"]);
+        assert!(storyboard.files().iter().all(|file| !file.contains("..")));
+        assert_eq!(
+            storyboard.relocated("storyboard/1"),
+            ["Sprite,Foreground,Centre,\"storyboard/1/sb/star.png\",320,240", " F,0,0,500,1,0"]
+        );
+    }
+}
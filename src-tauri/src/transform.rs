@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+
+use crate::osu_file::{Event, OsuFile};
+
+/// Re-times a chart for playback at `rate` times the original speed: every
+/// object, timing point, break, bookmark, the preview point and the audio
+/// lead-in move to their new time and uninherited beat lengths shrink so BPM and slider lengths
+/// follow. Storyboard commands are left alone; callers strip them.
+pub fn change_rate(osu: &mut OsuFile, rate: f64) {
+    let scale = |time: i32| (f64::from(time) / rate).round() as i32;
+
+    for object in osu.hit_objects_mut() {
+        object.time = scale(object.time);
+        object.end_time = object.end_time.map(scale);
+    }
+    for point in osu.timing_points_mut() {
+        point.time /= rate;
+        if point.uninherited {
+            point.beat_length /= rate;
+        }
+    }
+    for event in osu.events_mut() {
+        match event {
+            Event::Break { start_time, end_time } => {
+                *start_time = scale(*start_time);
+                *end_time = scale(*end_time);
+            }
+            Event::Video { start_time, .. } => *start_time = scale(*start_time),
+            _ => {}
+        }
+    }
+
+    if let Some(preview) = osu.get_parsed::<i32>("General", "PreviewTime").filter(|time| *time > 0) {
+        osu.set("General", "PreviewTime", scale(preview));
+    }
+    if let Some(lead_in) = osu.get_parsed::<i32>("General", "AudioLeadIn").filter(|time| *time > 0) {
+        osu.set("General", "AudioLeadIn", scale(lead_in));
+    }
+    if let Some(bookmarks) = osu.get("Editor", "Bookmarks").filter(|value| !value.trim().is_empty()) {
+        let scaled: Vec<String> = bookmarks
+            .split(',')
+            .filter_map(|time| time.trim().parse::<i32>().ok())
+            .map(|time| scale(time).to_string())
+            .collect();
+        osu.set("Editor", "Bookmarks", scaled.join(","));
+    }
+}
+
+/// The "(1.2x)" suffix appended to the Version of rate-changed difficulties.
+pub fn rate_suffix(rate: f64) -> String {
+    format!("({}x)", rate)
+}
+
+/// Rearranges the columns of a mania chart.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ColumnTransform {
+    /// Flips the chart left to right.
+    Mirror,
+    /// `mapping[i]` is the zero-based column original column `i` moves to.
+    Permute { mapping: Vec<u32> },
+    /// A random permutation drawn from `seed`, so the same seed always
+    /// rebuilds the same chart.
+    Shuffle { seed: u64 },
+}
+
+impl ColumnTransform {
+    /// The column every original column moves to, checked against `key_count`.
+    pub fn mapping(&self, key_count: u32) -> Result<Vec<u32>, String> {
+        match self {
+            ColumnTransform::Mirror => Ok((0..key_count).rev().collect()),
+            ColumnTransform::Permute { mapping } => {
+                let mut sorted = mapping.clone();
+                sorted.sort_unstable();
+                if sorted != (0..key_count).collect::<Vec<_>>() {
+                    return Err(format!(
+                        "Column order {} is not a rearrangement of {} columns",
+                        column_list(mapping),
+                        key_count
+                    ));
+                }
+                Ok(mapping.clone())
+            }
+            ColumnTransform::Shuffle { seed } => {
+                let mut mapping: Vec<u32> = (0..key_count).collect();
+                let mut rng = SplitMix64(*seed);
+                for i in (1..mapping.len()).rev() {
+                    let j = (rng.next() % (i as u64 + 1)) as usize;
+                    mapping.swap(i, j);
+                }
+                Ok(mapping)
+            }
+        }
+    }
+
+    /// Version suffix naming the variant, e.g. "(Mirror)" or "(Shuffle 42)".
+    pub fn suffix(&self) -> String {
+        match self {
+            ColumnTransform::Mirror => "(Mirror)".into(),
+            ColumnTransform::Permute { mapping } => format!("(Columns {})", column_list(mapping)),
+            ColumnTransform::Shuffle { seed } => format!("(Shuffle {})", seed),
+        }
+    }
+}
+
+/// One-based column numbers the way players write them: "2143", or
+/// "2-1-10-..." once a column needs two digits.
+fn column_list(mapping: &[u32]) -> String {
+    let separator = if mapping.len() > 9 { "-" } else { "" };
+    let columns: Vec<String> = mapping.iter().map(|column| (column + 1).to_string()).collect();
+    columns.join(separator)
+}
+
+/// Moves every note of a `key_count`-key mania chart to `mapping[column]`.
+pub fn remap_columns(osu: &mut OsuFile, mapping: &[u32], key_count: u32) {
+    for object in osu.hit_objects_mut() {
+        let column = object.column(key_count);
+        if let Some(&target) = mapping.get(column as usize) {
+            object.set_column(target, key_count);
+        }
+    }
+}
+
+/// Tiny deterministic generator; shuffles only need to be repeatable, not
+/// cryptographic.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHART: &str = concat!(
+        "osu file format v14\n",
+        "\n",
+        "[General]\n",
+        "AudioLeadIn: 1500\n",
+        "PreviewTime: 3000\n",
+        "Mode: 3\n",
+        "\n",
+        "[Editor]\n",
+        "Bookmarks: 1000,2001\n",
+        "\n",
+        "[Difficulty]\n",
+        "CircleSize:4\n",
+        "\n",
+        "[Events]\n",
+        "Video,-200,\"clip.avi\"\n",
+        "2,1000,2000\n",
+        "\n",
+        "[TimingPoints]\n",
+        "0,500,4,2,1,60,1,0\n",
+        "1000,-50,4,2,1,60,0,0\n",
+        "\n",
+        "[HitObjects]\n",
+        "64,192,1000,1,0,0:0:0:0:\n",
+        "448,192,1500,128,0,2501:0:0:0:0:\n",
+    );
+
+    #[test]
+    fn change_rate_moves_every_time_and_shortens_beats() {
+        let mut osu = OsuFile::parse(CHART);
+        change_rate(&mut osu, 2.0);
+
+        assert_eq!(osu.get("General", "AudioLeadIn"), Some("750"));
+        assert_eq!(osu.get("General", "PreviewTime"), Some("1500"));
+        assert_eq!(osu.get("Editor", "Bookmarks"), Some("500,1001"));
+        let written = osu.to_string();
+        assert!(written.contains("Video,-100,\"clip.avi\",0,0\n"), "{}", written);
+        assert!(written.contains("2,500,1000\n"), "{}", written);
+
+        let points: Vec<_> = osu.timing_points().map(|point| (point.time, point.beat_length)).collect();
+        // Inherited points keep their slider velocity multiplier.
+        assert_eq!(points, [(0.0, 250.0), (500.0, -50.0)]);
+        let objects: Vec<_> = osu.hit_objects().map(|object| (object.time, object.end_time())).collect();
+        assert_eq!(objects, [(500, 500), (750, 1251)]);
+    }
+
+    #[test]
+    fn mappings_are_permutations_of_the_columns() {
+        assert_eq!(ColumnTransform::Mirror.mapping(4).unwrap(), [3, 2, 1, 0]);
+        let permute = ColumnTransform::Permute { mapping: vec![1, 0, 3, 2] };
+        assert_eq!(permute.mapping(4).unwrap(), [1, 0, 3, 2]);
+        assert!(permute.mapping(5).is_err());
+        let repeated = ColumnTransform::Permute { mapping: vec![0, 0, 1, 2] };
+        assert_eq!(
+            repeated.mapping(4).unwrap_err(),
+            "Column order 1123 is not a rearrangement of 4 columns"
+        );
+
+        let shuffle = ColumnTransform::Shuffle { seed: 42 };
+        let mapping = shuffle.mapping(7).unwrap();
+        assert_eq!(mapping, shuffle.mapping(7).unwrap());
+        let mut sorted = mapping.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn suffixes_name_the_variant() {
+        assert_eq!(ColumnTransform::Mirror.suffix(), "(Mirror)");
+        assert_eq!(ColumnTransform::Shuffle { seed: 7 }.suffix(), "(Shuffle 7)");
+        let wide = ColumnTransform::Permute { mapping: (0..10).rev().collect() };
+        assert_eq!(wide.suffix(), "(Columns 10-9-8-7-6-5-4-3-2-1)");
+        assert_eq!(rate_suffix(1.25), "(1.25x)");
+    }
+
+    #[test]
+    fn remap_moves_notes_to_their_new_columns() {
+        let mut osu = OsuFile::parse(CHART);
+        remap_columns(&mut osu, &[3, 2, 1, 0], 4);
+        let columns: Vec<u32> = osu.hit_objects().map(|object| object.column(4)).collect();
+        assert_eq!(columns, [3, 0]);
+    }
+}